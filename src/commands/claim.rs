@@ -3,13 +3,13 @@ use git2::Repository;
 use crate::error::Error;
 use crate::snapshot;
 use crate::storage;
-use crate::types::Status;
+use crate::types::{Issue, Status};
 
-pub fn run(id_prefix: String, context: Option<String>) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
-
-    let id = snapshot::find_issue_id(&repo, &id_prefix)?;
-    let mut issue = snapshot::load_issue(&repo, &id)?;
+/// Claim an issue, moving it to in_progress
+pub fn claim(repo: &Repository, id_prefix: &str, context: Option<String>) -> Result<Issue, Error> {
+    let id = snapshot::find_issue_id(repo, id_prefix)?;
+    let before = snapshot::load_issue(repo, &id)?;
+    let mut issue = before.clone();
 
     if issue.claimed {
         return Err(Error::AlreadyClaimed(id));
@@ -18,16 +18,24 @@ pub fn run(id_prefix: String, context: Option<String>) -> Result<(), Error> {
         return Err(Error::IsClosed(id));
     }
 
+    let editor = storage::get_editor(repo)?;
     issue.claimed = true;
+    issue.claimed_by = Some(editor.clone());
     issue.status = Status::InProgress;
     issue.claim_context = context;
     issue.claimed_at = Some(chrono::Utc::now().timestamp() as u64);
-    issue.lamport += 1;
-    issue.updated_at = chrono::Utc::now().timestamp();
-    issue.editor = storage::get_editor()?;
+    issue.record_edit(&before, editor, snapshot::clock(repo)?);
+
+    snapshot::save_issue(repo, &issue, &format!("Claim issue {id}"))?;
+
+    Ok(issue)
+}
+
+pub fn run(id_prefix: String, context: Option<String>) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
 
-    snapshot::save_issue(&repo, &issue, &format!("Claim issue {id}"))?;
+    let issue = claim(&repo, &id_prefix, context)?;
 
-    println!("Claimed {id}");
+    println!("Claimed {}", issue.id);
     Ok(())
 }
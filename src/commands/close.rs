@@ -3,13 +3,13 @@ use git2::Repository;
 use crate::error::Error;
 use crate::snapshot;
 use crate::storage;
-use crate::types::Status;
+use crate::types::{Issue, Status};
 
-pub fn run(id_prefix: String, reason: Option<String>) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
-
-    let id = snapshot::find_issue_id(&repo, &id_prefix)?;
-    let mut issue = snapshot::load_issue(&repo, &id)?;
+/// Close an issue, dropping any claim
+pub fn close(repo: &Repository, id_prefix: &str, reason: Option<String>) -> Result<Issue, Error> {
+    let id = snapshot::find_issue_id(repo, id_prefix)?;
+    let before = snapshot::load_issue(repo, &id)?;
+    let mut issue = before.clone();
 
     if issue.status == Status::Closed {
         return Err(Error::AlreadyClosed(id));
@@ -17,15 +17,22 @@ pub fn run(id_prefix: String, reason: Option<String>) -> Result<(), Error> {
 
     issue.status = Status::Closed;
     issue.claimed = false;
+    issue.claimed_by = None;
     issue.claim_context = None;
     issue.claimed_at = None;
     issue.reason = reason;
-    issue.lamport += 1;
-    issue.updated_at = chrono::Utc::now().timestamp();
-    issue.editor = storage::get_editor()?;
+    issue.record_edit(&before, storage::get_editor(repo)?, snapshot::clock(repo)?);
+
+    snapshot::save_issue(repo, &issue, &format!("Close issue {id}"))?;
+
+    Ok(issue)
+}
+
+pub fn run(id_prefix: String, reason: Option<String>) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
 
-    snapshot::save_issue(&repo, &issue, &format!("Close issue {id}"))?;
+    let issue = close(&repo, &id_prefix, reason)?;
 
-    println!("Closed {id}");
+    println!("Closed {}", issue.id);
     Ok(())
 }
@@ -0,0 +1,300 @@
+use git2::Repository;
+
+use crate::error::Error;
+use crate::snapshot::{self, Changeset};
+use crate::storage;
+use crate::types::{Conflict, Issue, Resolution};
+
+/// Open conflict records, sorted by issue id, oldest first per issue
+pub fn list(repo: &Repository) -> Result<Vec<Conflict>, Error> {
+    let mut conflicts: Vec<Conflict> = snapshot::load_conflicts(repo)?
+        .into_iter()
+        .filter(|c| c.resolution.is_none())
+        .collect();
+    conflicts.sort_by(|a, b| (&a.id, a.lamport).cmp(&(&b.id, b.lamport)));
+    Ok(conflicts)
+}
+
+/// Settle an open conflict by entry name (id_lamport) or unique prefix, so
+/// an issue id works while the issue has a single open record. `Local` and
+/// `Remote` write that side's value of each discarded field group back to
+/// the issue; `Merged` keeps the issue as the merge left it. The record is
+/// kept, marked resolved. Works on deleted issues too. Returns the resolved
+/// record.
+pub fn resolve(repo: &Repository, name_prefix: &str, take: Resolution) -> Result<Conflict, Error> {
+    let open = list(repo)?;
+    let matches: Vec<&Conflict> = open
+        .iter()
+        .filter(|c| snapshot::conflict_name(c).starts_with(name_prefix))
+        .collect();
+    let mut conflict = match matches.as_slice() {
+        [] => return Err(Error::NoConflict(name_prefix.to_string())),
+        [conflict] => (*conflict).clone(),
+        _ => {
+            return Err(Error::AmbiguousId(
+                name_prefix.to_string(),
+                matches.iter().map(|c| snapshot::conflict_name(c)).collect(),
+            ));
+        }
+    };
+    let id = conflict.id.clone();
+    let clock = snapshot::clock(repo)?;
+    let editor = storage::get_editor(repo)?;
+
+    // The issue may have been deleted since; its tombstone still takes the
+    // chosen fields, so they come back with it if the deletion is undone
+    let chosen = match take {
+        Resolution::Local => Some(&conflict.local),
+        Resolution::Remote => Some(&conflict.remote),
+        Resolution::Merged => None,
+    };
+    let mut changes = Changeset::default();
+    if let Some(chosen) = chosen {
+        let before = snapshot::load_all_issues(repo)?
+            .remove(&id)
+            .ok_or_else(|| Error::NotFound(id.clone()))?;
+        let mut issue = before.clone();
+        for field in &conflict.fields {
+            take_field(&mut issue, chosen, field);
+        }
+        if issue != before {
+            issue.record_edit(&before, editor.clone(), clock);
+            changes.issues.push(issue);
+        }
+    }
+    conflict.resolution = Some(take);
+    conflict.editor = editor;
+    changes.conflicts.push(conflict.clone());
+
+    snapshot::commit_changes(
+        repo,
+        &changes,
+        &format!("Resolve conflict on {id}: take {}", take.as_str()),
+    )?;
+    Ok(conflict)
+}
+
+/// Copy one field group from `from`, with the fields that change alongside it
+fn take_field(issue: &mut Issue, from: &Issue, field: &str) {
+    match field {
+        "title" => issue.title = from.title.clone(),
+        "description" => {
+            issue.description = from.description.clone();
+            issue.conflict = from.conflict;
+        }
+        "status" => {
+            issue.status = from.status;
+            issue.claimed = from.claimed;
+            issue.claimed_by = from.claimed_by.clone();
+            issue.claim_context = from.claim_context.clone();
+            issue.claimed_at = from.claimed_at;
+            issue.reason = from.reason.clone();
+        }
+        "priority" => issue.priority = from.priority,
+        "type" => issue.issue_type = from.issue_type,
+        _ => {}
+    }
+}
+
+/// One field group's value, shortened to fit a table cell
+fn field_value(issue: &Issue, field: &str) -> String {
+    let value = match field {
+        "title" => issue.title.clone(),
+        "description" => issue.description.lines().next().unwrap_or("").to_string(),
+        "status" => match &issue.reason {
+            Some(reason) => format!("{} ({reason})", issue.status.as_str()),
+            None if issue.claimed => format!("{} (claimed)", issue.status.as_str()),
+            None => issue.status.as_str().to_string(),
+        },
+        "priority" => issue.priority.as_str().to_string(),
+        "type" => issue.issue_type.as_str().to_string(),
+        _ => String::new(),
+    };
+    truncate(&value, 30)
+}
+
+fn truncate(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
+        s.to_string()
+    } else {
+        let cut: String = s.chars().take(max_len - 3).collect();
+        format!("{cut}...")
+    }
+}
+
+pub fn run(json: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let conflicts = list(&repo)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&conflicts)?);
+        return Ok(());
+    }
+
+    if conflicts.is_empty() {
+        println!("No open conflicts");
+        return Ok(());
+    }
+    let issues = snapshot::load_all_issues(&repo)?;
+    for conflict in &conflicts {
+        println!(
+            "{}: {} ({})",
+            snapshot::conflict_name(conflict),
+            conflict.title,
+            conflict.source
+        );
+
+        let mut rows = vec![[
+            "field".to_string(),
+            format!("local ({})", conflict.local.editor),
+            format!("remote ({})", conflict.remote.editor),
+            "current".to_string(),
+        ]];
+        for field in &conflict.fields {
+            rows.push([
+                field.clone(),
+                field_value(&conflict.local, field),
+                field_value(&conflict.remote, field),
+                issues
+                    .get(&conflict.id)
+                    .map(|i| field_value(i, field))
+                    .unwrap_or_default(),
+            ]);
+        }
+        let widths: Vec<usize> = (0..3)
+            .map(|col| {
+                rows.iter()
+                    .map(|r| r[col].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        for row in &rows {
+            println!(
+                "    {:<w0$}  {:<w1$}  {:<w2$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2]
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn run_resolve(name_prefix: String, take: String) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let take: Resolution = take.parse()?;
+    let conflict = resolve(&repo, &name_prefix, take)?;
+
+    println!(
+        "Resolved conflict on {} ({}): took {} {}",
+        conflict.id,
+        conflict.title,
+        take.as_str(),
+        conflict.fields.join(", ")
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{claim, close, export, import, pull, push, update};
+    use crate::testing::clones;
+    use crate::types::Priority;
+
+    fn set(repo: &Repository, id: &str, title: &str, priority: Priority) {
+        let changes = update::Changes {
+            title: Some(title.to_string()),
+            priority: Some(priority),
+            ..Default::default()
+        };
+        update::update(repo, id, changes).unwrap();
+    }
+
+    /// Concurrent title, priority and status edits on both clones, pulled
+    /// into `b`. Returns b and the recorded conflict.
+    fn conflicted() -> (tempfile::TempDir, Repository, String, Conflict) {
+        let (dir, a, b, id) = clones();
+        set(&a, &id, "From a", Priority::High);
+        claim::claim(&a, &id, None).unwrap();
+        push::push(&a, "origin").unwrap();
+        set(&b, &id, "From b", Priority::Low);
+        close::close(&b, &id, Some("done".to_string())).unwrap();
+
+        let summary = pull::pull(&b, "origin", false).unwrap();
+        assert_eq!(summary.conflicts.len(), 1);
+        let open = list(&b).unwrap();
+        assert_eq!(open.len(), 1);
+        (dir, b, id, open[0].clone())
+    }
+
+    fn assert_took(issue: &Issue, side: &Issue) {
+        assert_eq!(issue.title, side.title);
+        assert_eq!(issue.priority, side.priority);
+        assert_eq!(issue.status, side.status);
+        assert_eq!(issue.claimed_by, side.claimed_by);
+        assert_eq!(issue.reason, side.reason);
+    }
+
+    #[test]
+    fn test_pull_records_discarded_version() {
+        let (_dir, _b, id, conflict) = conflicted();
+        assert_eq!(conflict.id, id);
+        assert_eq!(conflict.fields, vec!["title", "status", "priority"]);
+        assert_eq!(conflict.local.title, "From b");
+        assert_eq!(conflict.remote.title, "From a");
+        assert!(conflict.resolution.is_none());
+    }
+
+    #[test]
+    fn test_import_records_discarded_version() {
+        let (_dir, a, b, id) = clones();
+        set(&a, &id, "From a", Priority::High);
+        set(&b, &id, "From b", Priority::Low);
+
+        let summary = import::import(&b, export::export(&a, None).unwrap(), false).unwrap();
+        assert_eq!(summary.conflicts.len(), 1);
+        let open = list(&b).unwrap();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].fields, vec!["title", "priority"]);
+    }
+
+    #[test]
+    fn test_resolve_take_local_and_remote() {
+        for take in [Resolution::Local, Resolution::Remote] {
+            let (_dir, b, id, conflict) = conflicted();
+            let resolved = resolve(&b, &id, take).unwrap();
+
+            let side = match take {
+                Resolution::Local => &conflict.local,
+                _ => &conflict.remote,
+            };
+            assert_took(&snapshot::load_issue(&b, &id).unwrap(), side);
+            assert_eq!(resolved.resolution, Some(take));
+            assert!(list(&b).unwrap().is_empty());
+            let stored = snapshot::load_conflicts(&b).unwrap();
+            assert_eq!(stored.len(), 1);
+            assert_eq!(stored[0].resolution, Some(take));
+        }
+    }
+
+    #[test]
+    fn test_resolve_take_merged_keeps_issue() {
+        let (_dir, b, id, conflict) = conflicted();
+        let before = snapshot::load_issue(&b, &id).unwrap();
+
+        let name = snapshot::conflict_name(&conflict);
+        resolve(&b, &name, Resolution::Merged).unwrap();
+        assert_eq!(snapshot::load_issue(&b, &id).unwrap(), before);
+        assert!(list(&b).unwrap().is_empty());
+        assert!(matches!(
+            resolve(&b, &name, Resolution::Local),
+            Err(Error::NoConflict(_))
+        ));
+    }
+}
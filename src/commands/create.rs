@@ -4,59 +4,84 @@ use crate::error::Error;
 use crate::id;
 use crate::snapshot;
 use crate::storage;
-use crate::types::{Issue, IssueType, Priority, SCHEMA_VERSION, Status};
+use crate::types::{FieldClocks, Issue, IssueType, LabelSet, Priority, SCHEMA_VERSION, Status};
 
-pub fn run(
+/// Create a new open issue and return it
+pub fn create(
+    repo: &Repository,
     title: String,
-    description: Option<String>,
-    priority: Option<String>,
-    issue_type: Option<String>,
+    description: String,
+    priority: Priority,
+    issue_type: IssueType,
     labels: Vec<String>,
-) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
-    let editor = storage::get_editor()?;
+) -> Result<Issue, Error> {
+    let editor = storage::get_editor(repo)?;
 
-    let existing_ids = snapshot::get_existing_ids(&repo)?;
+    let existing_ids = snapshot::get_existing_ids(repo)?;
 
-    let id = id::generate_id(
-        &title,
-        description.as_deref().unwrap_or(""),
-        &editor,
-        &existing_ids,
-    );
+    let id = id::generate_id(&title, &description, &editor, &existing_ids);
     let now = chrono::Utc::now().timestamp();
-
-    let priority = match priority {
-        Some(p) => Priority::from_str(&p)?,
-        None => Priority::Medium,
-    };
-
-    let issue_type = match issue_type {
-        Some(t) => IssueType::from_str(&t)?,
-        None => IssueType::Task,
-    };
+    let lamport = snapshot::clock(repo)? + 1;
+    let mut label_tags = LabelSet::default();
+    label_tags.update(&labels, &LabelSet::tag(lamport, &editor));
 
     let issue = Issue {
         schema_version: SCHEMA_VERSION,
         id: id.clone(),
         title,
-        description: description.unwrap_or_default(),
+        description,
+        conflict: false,
         status: Status::Open,
         priority,
         issue_type,
-        labels,
+        labels: label_tags.labels(),
+        label_tags,
         created_at: now,
         updated_at: now,
-        lamport: 1,
+        lamport,
         editor,
         claimed: false,
+        claimed_by: None,
         claim_context: None,
         claimed_at: None,
         reason: None,
+        deleted: false,
+        clocks: FieldClocks::at(lamport),
     };
 
-    snapshot::save_issue(&repo, &issue, &format!("Create issue {id}"))?;
+    snapshot::save_issue(repo, &issue, &format!("Create issue {id}"))?;
+
+    Ok(issue)
+}
+
+pub fn run(
+    title: String,
+    description: Option<String>,
+    priority: Option<String>,
+    issue_type: Option<String>,
+    labels: Vec<String>,
+) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let priority = match priority {
+        Some(p) => p.parse()?,
+        None => Priority::Medium,
+    };
+
+    let issue_type = match issue_type {
+        Some(t) => t.parse()?,
+        None => IssueType::Task,
+    };
+
+    let issue = create(
+        &repo,
+        title,
+        description.unwrap_or_default(),
+        priority,
+        issue_type,
+        labels,
+    )?;
 
-    println!("{id}");
+    println!("{}", issue.id);
     Ok(())
 }
@@ -0,0 +1,54 @@
+use git2::Repository;
+
+use crate::error::Error;
+use crate::snapshot::{self, Changeset};
+use crate::storage;
+use crate::types::{Edge, Issue};
+
+/// Delete an issue by writing a tombstone, and remove every edge that
+/// references it in the same commit. Returns the tombstone and the removed edges.
+pub fn delete(repo: &Repository, id_prefix: &str) -> Result<(Issue, Vec<Edge>), Error> {
+    let id = snapshot::find_issue_id(repo, id_prefix)?;
+    let before = snapshot::load_issue(repo, &id)?;
+    let mut issue = before.clone();
+
+    let editor = storage::get_editor(repo)?;
+    let clock = snapshot::clock(repo)?;
+    issue.deleted = true;
+    issue.record_edit(&before, editor.clone(), clock);
+
+    let removed: Vec<Edge> = snapshot::load_edges(repo)?
+        .into_iter()
+        .filter(|e| e.source == id || e.target == id)
+        .collect();
+
+    let mut changes = Changeset::default();
+    changes.issues.push(issue.clone());
+    for edge in &removed {
+        let mut tombstone = edge.clone();
+        tombstone.deleted = true;
+        tombstone.record_edit(editor.clone(), clock);
+        changes.edges.push(tombstone);
+    }
+
+    snapshot::commit_changes(repo, &changes, &format!("Delete issue {id}"))?;
+
+    Ok((issue, removed))
+}
+
+pub fn run(id_prefix: String) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let (issue, removed) = delete(&repo, &id_prefix)?;
+
+    println!("Deleted {}", issue.id);
+    for edge in &removed {
+        println!(
+            "  removed edge {} {} {}",
+            edge.source,
+            edge.edge_type.as_str(),
+            edge.target
+        );
+    }
+    Ok(())
+}
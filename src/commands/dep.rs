@@ -3,64 +3,122 @@ use git2::Repository;
 use crate::dag;
 use crate::error::Error;
 use crate::snapshot;
+use crate::storage;
 use crate::types::{Edge, EdgeType, SCHEMA_VERSION};
 
-pub fn add(
-    source: String,
-    needs: Option<String>,
-    blocks: Option<String>,
-    relates_to: Option<String>,
-    parent: Option<String>,
-    duplicates: Option<String>,
-) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
-
-    let source_id = snapshot::find_issue_id(&repo, &source)?;
-
-    let (target_prefix, edge_type) = if let Some(t) = needs {
-        (t, EdgeType::DependsOn)
-    } else if let Some(t) = blocks {
-        (t, EdgeType::Blocks)
-    } else if let Some(t) = relates_to {
-        (t, EdgeType::RelatesTo)
-    } else if let Some(t) = parent {
-        (t, EdgeType::ParentChild)
-    } else if let Some(t) = duplicates {
-        (t, EdgeType::Duplicates)
-    } else {
-        return Err(Error::NoEdgeTarget);
-    };
-
-    let target_id = snapshot::find_issue_id(&repo, &target_prefix)?;
+/// Add an edge between two issues, rejecting duplicates and cycles
+pub fn add_edge(
+    repo: &Repository,
+    source: &str,
+    target: &str,
+    edge_type: EdgeType,
+) -> Result<Edge, Error> {
+    let source_id = snapshot::find_issue_id(repo, source)?;
+    let target_id = snapshot::find_issue_id(repo, target)?;
 
     if source_id == target_id {
         return Err(Error::SelfReference(source_id));
     }
 
-    if snapshot::edge_exists(&repo, &source_id, &target_id, edge_type)? {
+    if snapshot::edge_exists(repo, &source_id, &target_id, edge_type)? {
         return Err(Error::DuplicateEdge(source_id, target_id));
     }
 
-    let edges = snapshot::load_edges(&repo)?;
+    let edges = snapshot::load_edges(repo)?;
     if dag::would_create_cycle(&edges, &source_id, &target_id, edge_type) {
         return Err(Error::WouldCreateCycle(source_id, target_id));
     }
 
-    let edge = Edge {
+    // Re-adding a removed edge revives its tombstone so the add wins on merge
+    let tombstone = find_edge(repo, &source_id, &target_id, edge_type)?;
+    let mut edge = Edge {
         schema_version: SCHEMA_VERSION,
         source: source_id.clone(),
         target: target_id.clone(),
         edge_type,
         created_at: chrono::Utc::now().timestamp(),
+        lamport: tombstone.map_or(0, |e| e.lamport),
+        editor: String::new(),
+        deleted: false,
+        quarantine: None,
     };
+    edge.record_edit(storage::get_editor(repo)?, snapshot::clock(repo)?);
 
     snapshot::save_edge(
-        &repo,
+        repo,
         &edge,
         &format!("{} {} {}", source_id, edge_type.as_str(), target_id),
     )?;
 
-    println!("{} {} {}", source_id, edge_type.as_str(), target_id);
+    Ok(edge)
+}
+
+/// Remove an edge between two issues. Returns false if it did not exist.
+pub fn remove_edge(
+    repo: &Repository,
+    source: &str,
+    target: &str,
+    edge_type: EdgeType,
+) -> Result<bool, Error> {
+    let source_id = snapshot::find_issue_id(repo, source)?;
+    let target_id = snapshot::find_issue_id(repo, target)?;
+
+    let Some(mut edge) = find_edge(repo, &source_id, &target_id, edge_type)? else {
+        return Ok(false);
+    };
+    if edge.deleted {
+        return Ok(false);
+    }
+
+    // Keep a tombstone so the removal survives pulls from clones that still have it
+    edge.deleted = true;
+    edge.record_edit(storage::get_editor(repo)?, snapshot::clock(repo)?);
+
+    snapshot::save_edge(
+        repo,
+        &edge,
+        &format!(
+            "Remove edge: {} {} {}",
+            source_id,
+            edge_type.as_str(),
+            target_id
+        ),
+    )?;
+    Ok(true)
+}
+
+/// Look up an edge by key, including tombstones
+fn find_edge(
+    repo: &Repository,
+    source: &str,
+    target: &str,
+    edge_type: EdgeType,
+) -> Result<Option<Edge>, Error> {
+    Ok(snapshot::load_all_edges(repo)?
+        .into_iter()
+        .find(|e| e.source == source && e.target == target && e.edge_type == edge_type))
+}
+
+pub fn add(
+    source: String,
+    needs: Option<String>,
+    blocks: Option<String>,
+    relates_to: Option<String>,
+    parent: Option<String>,
+    duplicates: Option<String>,
+) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let (target_prefix, edge_type) = edge_target(needs, blocks, relates_to, parent, duplicates)?;
+
+    let edge = add_edge(&repo, &source, &target_prefix, edge_type)?;
+
+    println!(
+        "{} {} {}",
+        edge.source,
+        edge.edge_type.as_str(),
+        edge.target
+    );
     Ok(())
 }
 
@@ -74,35 +132,12 @@ pub fn remove(
 ) -> Result<(), Error> {
     let repo = Repository::discover(".")?;
 
-    let (target_prefix, edge_type) = if let Some(t) = needs {
-        (t, EdgeType::DependsOn)
-    } else if let Some(t) = blocks {
-        (t, EdgeType::Blocks)
-    } else if let Some(t) = relates_to {
-        (t, EdgeType::RelatesTo)
-    } else if let Some(t) = parent {
-        (t, EdgeType::ParentChild)
-    } else if let Some(t) = duplicates {
-        (t, EdgeType::Duplicates)
-    } else {
-        return Err(Error::NoEdgeTarget);
-    };
+    let (target_prefix, edge_type) = edge_target(needs, blocks, relates_to, parent, duplicates)?;
 
     let source_id = snapshot::find_issue_id(&repo, &source)?;
     let target_id = snapshot::find_issue_id(&repo, &target_prefix)?;
 
-    let deleted = snapshot::delete_edge(
-        &repo,
-        &source_id,
-        &target_id,
-        edge_type,
-        &format!(
-            "Remove edge: {} {} {}",
-            source_id,
-            edge_type.as_str(),
-            target_id
-        ),
-    )?;
+    let deleted = remove_edge(&repo, &source_id, &target_id, edge_type)?;
 
     if deleted {
         println!(
@@ -121,3 +156,26 @@ pub fn remove(
     }
     Ok(())
 }
+
+/// Pick the target and edge type from the mutually exclusive CLI flags
+fn edge_target(
+    needs: Option<String>,
+    blocks: Option<String>,
+    relates_to: Option<String>,
+    parent: Option<String>,
+    duplicates: Option<String>,
+) -> Result<(String, EdgeType), Error> {
+    if let Some(t) = needs {
+        Ok((t, EdgeType::DependsOn))
+    } else if let Some(t) = blocks {
+        Ok((t, EdgeType::Blocks))
+    } else if let Some(t) = relates_to {
+        Ok((t, EdgeType::RelatesTo))
+    } else if let Some(t) = parent {
+        Ok((t, EdgeType::ParentChild))
+    } else if let Some(t) = duplicates {
+        Ok((t, EdgeType::Duplicates))
+    } else {
+        Err(Error::NoEdgeTarget)
+    }
+}
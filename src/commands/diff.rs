@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use git2::Repository;
+use serde::Serialize;
+
+use crate::commands::export::Export;
+use crate::error::Error;
+use crate::snapshot;
+use crate::types::{Edge, FieldChange, Issue};
+
+/// Live issues and edges of one side of a diff
+#[derive(Debug, Clone)]
+pub struct TrackerState {
+    /// What the side was resolved from, e.g. `snapshot 1a2b3c4`
+    pub label: String,
+    pub issues: HashMap<String, Issue>,
+    pub edges: Vec<Edge>,
+}
+
+impl TrackerState {
+    /// No issues and no edges, e.g. before a first pull
+    pub fn empty(label: &str) -> Self {
+        TrackerState {
+            label: label.to_string(),
+            issues: HashMap::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    /// This state after writing `issues` and `edges` (tombstones included)
+    pub fn with_changes(&self, label: String, issues: &[Issue], edges: &[Edge]) -> Self {
+        let mut state = TrackerState {
+            label,
+            ..self.clone()
+        };
+        for issue in issues {
+            if issue.deleted {
+                state.issues.remove(&issue.id);
+            } else {
+                state.issues.insert(issue.id.clone(), issue.clone());
+            }
+        }
+        for edge in edges {
+            state.edges.retain(|e| !e.same_key(edge));
+            if edge.is_live() {
+                state.edges.push(edge.clone());
+            }
+        }
+        state
+    }
+}
+
+/// An issue present on both sides with different field values
+#[derive(Serialize, Debug, Clone)]
+pub struct IssueDiff {
+    pub id: String,
+    pub title: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct DiffReport {
+    pub from: String,
+    pub to: String,
+    pub issues_added: Vec<Issue>,
+    pub issues_removed: Vec<Issue>,
+    pub issues_changed: Vec<IssueDiff>,
+    pub edges_added: Vec<Edge>,
+    pub edges_removed: Vec<Edge>,
+}
+
+impl DiffReport {
+    pub fn is_empty(&self) -> bool {
+        self.issues_added.is_empty()
+            && self.issues_removed.is_empty()
+            && self.issues_changed.is_empty()
+            && self.edges_added.is_empty()
+            && self.edges_removed.is_empty()
+    }
+}
+
+/// Resolve one side of a diff. In order, `spec` may be an export file, the
+/// name of a fetched remote, or anything `--at` accepts (a snapshot commit or
+/// a date). Without a spec, `default_previous` picks the snapshot before the
+/// current one and otherwise the current snapshot.
+pub fn resolve(
+    repo: &Repository,
+    spec: Option<&str>,
+    default_previous: bool,
+) -> Result<TrackerState, Error> {
+    if let Some(spec) = spec
+        && Path::new(spec).is_file()
+    {
+        let export = Export::from_json(&fs::read(spec)?)?;
+        return Ok(TrackerState {
+            label: format!("export {spec}"),
+            issues: export
+                .issues
+                .into_iter()
+                .filter(|i| !i.deleted)
+                .map(|i| (i.id.clone(), i))
+                .collect(),
+            edges: export.edges.into_iter().filter(Edge::is_live).collect(),
+        });
+    }
+
+    let (label, commit) = match spec {
+        Some(spec) if repo.find_remote(spec).is_ok() => {
+            let commit = snapshot::remote_commit(repo, spec)
+                .ok_or_else(|| Error::RemoteNotFetched(spec.to_string()))?;
+            (format!("{spec} snapshot"), commit)
+        }
+        Some(spec) => (spec.to_string(), snapshot::resolve_at(repo, spec)?),
+        None => {
+            let current = snapshot::get_snapshot_commit(repo)?;
+            match current.parent(0) {
+                Ok(parent) if default_previous => ("previous snapshot".to_string(), parent),
+                _ => ("current snapshot".to_string(), current),
+            }
+        }
+    };
+    Ok(TrackerState {
+        label: format!("{label} ({})", &commit.id().to_string()[..7]),
+        issues: snapshot::load_issues_at(repo, &commit)?,
+        edges: snapshot::load_edges_at(repo, &commit)?,
+    })
+}
+
+/// Issues added, removed and changed (field by field) and edges added and
+/// removed going from `from` to `to`
+pub fn diff(from: &TrackerState, to: &TrackerState) -> DiffReport {
+    let mut report = DiffReport {
+        from: from.label.clone(),
+        to: to.label.clone(),
+        issues_added: Vec::new(),
+        issues_removed: Vec::new(),
+        issues_changed: Vec::new(),
+        edges_added: Vec::new(),
+        edges_removed: Vec::new(),
+    };
+
+    let ids: BTreeSet<&String> = from.issues.keys().chain(to.issues.keys()).collect();
+    for id in ids {
+        match (from.issues.get(id), to.issues.get(id)) {
+            (None, Some(new)) => report.issues_added.push(new.clone()),
+            (Some(old), None) => report.issues_removed.push(old.clone()),
+            (Some(old), Some(new)) => {
+                let changes = new.changes_from(old);
+                if !changes.is_empty() {
+                    report.issues_changed.push(IssueDiff {
+                        id: new.id.clone(),
+                        title: new.title.clone(),
+                        changes,
+                    });
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    let keyed = |edges: &[Edge]| -> BTreeMap<String, Edge> {
+        edges
+            .iter()
+            .map(|e| (snapshot::edge_name(e), e.clone()))
+            .collect()
+    };
+    let (old_edges, new_edges) = (keyed(&from.edges), keyed(&to.edges));
+    for (name, edge) in &new_edges {
+        if !old_edges.contains_key(name) {
+            report.edges_added.push(edge.clone());
+        }
+    }
+    for (name, edge) in &old_edges {
+        if !new_edges.contains_key(name) {
+            report.edges_removed.push(edge.clone());
+        }
+    }
+    report
+}
+
+pub fn run(from: Option<String>, to: Option<String>, json: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    if !snapshot::is_initialized(&repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let from_state = resolve(&repo, from.as_deref(), true)?;
+    let to_state = resolve(&repo, to.as_deref(), false)?;
+    let report = diff(&from_state, &to_state);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("--- {}", report.from);
+    println!("+++ {}", report.to);
+    if report.is_empty() {
+        println!("No differences");
+        return Ok(());
+    }
+    print_changes(&report);
+    Ok(())
+}
+
+/// One line per added, removed or changed issue and edge, with field changes
+/// indented below changed issues
+pub fn print_changes(report: &DiffReport) {
+    for issue in &report.issues_added {
+        println!("+ {:<12} {}", issue.id, issue.title);
+    }
+    for issue in &report.issues_removed {
+        println!("- {:<12} {}", issue.id, issue.title);
+    }
+    for issue in &report.issues_changed {
+        println!("~ {:<12} {}", issue.id, issue.title);
+        for change in &issue.changes {
+            if change.field == "description" {
+                println!("    description changed");
+            } else {
+                println!("    {}: {:?} -> {:?}", change.field, change.from, change.to);
+            }
+        }
+    }
+    for (sign, edges) in [("+", &report.edges_added), ("-", &report.edges_removed)] {
+        for edge in edges {
+            println!(
+                "{sign} edge {} {} {}",
+                edge.source,
+                edge.edge_type.as_str(),
+                edge.target
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{delete, dep, update};
+    use crate::testing::{create_issue, head, repo};
+    use crate::types::EdgeType;
+
+    #[test]
+    fn test_diff_between_snapshots() {
+        let (_dir, repo) = repo();
+        let a = create_issue(&repo, "A");
+        let b = create_issue(&repo, "B");
+        dep::add_edge(&repo, &a.id, &b.id, EdgeType::DependsOn).unwrap();
+        let before = head(&repo).id().to_string();
+
+        let changes = update::Changes {
+            title: Some("A renamed".to_string()),
+            ..Default::default()
+        };
+        update::update(&repo, &a.id, changes).unwrap();
+        delete::delete(&repo, &b.id).unwrap();
+        let c = create_issue(&repo, "C");
+        dep::add_edge(&repo, &a.id, &c.id, EdgeType::RelatesTo).unwrap();
+
+        let from = resolve(&repo, Some(&before), false).unwrap();
+        let to = resolve(&repo, None, false).unwrap();
+        assert!(from.label.starts_with(&before));
+        assert!(to.label.starts_with("current snapshot"));
+        let report = diff(&from, &to);
+
+        let ids = |issues: &[Issue]| issues.iter().map(|i| i.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&report.issues_added), vec![c.id.clone()]);
+        assert_eq!(ids(&report.issues_removed), vec![b.id.clone()]);
+        assert_eq!(report.issues_changed.len(), 1);
+        let changed = &report.issues_changed[0];
+        assert_eq!(changed.id, a.id);
+        assert_eq!(changed.changes.len(), 1);
+        assert_eq!(changed.changes[0].field, "title");
+        assert_eq!(changed.changes[0].from, "A");
+        assert_eq!(changed.changes[0].to, "A renamed");
+
+        let keys = |edges: &[Edge]| edges.iter().map(snapshot::edge_name).collect::<Vec<_>>();
+        assert_eq!(
+            keys(&report.edges_added),
+            vec![format!("{}_{}_relates_to", a.id, c.id)]
+        );
+        assert_eq!(
+            keys(&report.edges_removed),
+            vec![format!("{}_{}_depends_on", a.id, b.id)]
+        );
+
+        // The reverse diff swaps every side
+        let back = diff(&to, &from);
+        assert_eq!(ids(&back.issues_added), vec![b.id]);
+        assert_eq!(ids(&back.issues_removed), vec![c.id]);
+        assert_eq!(back.edges_added.len(), 1);
+        assert!(diff(&to, &to).is_empty());
+    }
+
+    #[test]
+    fn test_default_previous_is_parent_snapshot() {
+        let (_dir, repo) = repo();
+        let a = create_issue(&repo, "A");
+
+        let previous = resolve(&repo, None, true).unwrap();
+        let current = resolve(&repo, None, false).unwrap();
+        let report = diff(&previous, &current);
+        assert_eq!(report.issues_added.len(), 1);
+        assert_eq!(report.issues_added[0].id, a.id);
+    }
+}
@@ -1,32 +1,75 @@
 use std::fs;
 
-use git2::Repository;
+use git2::{Commit, Repository};
 use serde::Serialize;
 
 use crate::error::Error;
 use crate::snapshot;
 use crate::types::{Edge, Issue};
 
-#[derive(Serialize)]
-struct Export {
-    version: u32,
-    exported_at: i64,
-    issues: Vec<Issue>,
-    edges: Vec<Edge>,
+/// Portable dump of every issue and edge, as written by `st export`
+#[derive(Serialize, Debug, Clone)]
+pub struct Export {
+    pub version: u32,
+    pub exported_at: i64,
+    pub issues: Vec<Issue>,
+    pub edges: Vec<Edge>,
 }
 
-pub fn run(output: Option<String>) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
+impl Export {
+    /// Parse an export file, upgrading issues and edges from older schemas
+    pub fn from_json(data: &[u8]) -> Result<Self, Error> {
+        let value: serde_json::Value = serde_json::from_slice(data)?;
+        let list = |key: &str| match value.get(key) {
+            Some(serde_json::Value::Array(items)) => Ok(items.clone()),
+            _ => Err(Error::InvalidExport(format!("missing {key} array"))),
+        };
 
-    let issues: Vec<Issue> = snapshot::load_issues(&repo)?.into_values().collect();
-    let edges = snapshot::load_edges(&repo)?;
+        Ok(Export {
+            version: value["version"].as_u64().unwrap_or(0) as u32,
+            exported_at: value["exported_at"].as_i64().unwrap_or(0),
+            issues: list("issues")?
+                .into_iter()
+                .map(Issue::from_value)
+                .collect::<Result<_, _>>()?,
+            edges: list("edges")?
+                .into_iter()
+                .map(Edge::from_value)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Collect all issues and edges from the snapshot, optionally as of `at`.
+/// Tombstones are included so an import honors deletions and removals.
+pub fn export(repo: &Repository, at: Option<&Commit>) -> Result<Export, Error> {
+    let (issues, edges) = match at {
+        Some(commit) => (
+            snapshot::load_all_issues_at(repo, commit)?,
+            snapshot::load_all_edges_at(repo, commit)?,
+        ),
+        None => (
+            snapshot::load_all_issues(repo)?,
+            snapshot::load_all_edges(repo)?,
+        ),
+    };
+    let issues: Vec<Issue> = issues.into_values().collect();
 
-    let export = Export {
+    Ok(Export {
         version: 1,
         exported_at: chrono::Utc::now().timestamp(),
         issues,
         edges,
-    };
+    })
+}
+
+pub fn run(at: Option<String>, output: Option<String>) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let at = at
+        .map(|spec| snapshot::resolve_at(&repo, &spec))
+        .transpose()?;
+
+    let export = export(&repo, at.as_ref())?;
 
     let json = serde_json::to_string_pretty(&export)?;
 
@@ -0,0 +1,68 @@
+use git2::{Commit, Repository};
+
+use crate::error::Error;
+use crate::snapshot;
+use crate::storage;
+
+/// Scratch ref every fetch lands in first, so a remote without a snapshot
+/// can be told apart from a stale tracking ref
+const FETCH_REF: &str = "refs/sterna/fetched";
+
+/// Fetch a remote's snapshot into `refs/sterna/remotes/<remote>/snapshot`
+/// without merging it, and return the fetched commit. `remote_name` may also
+/// be a URL or path; such one-off remotes get no tracking ref.
+pub fn fetch<'a>(repo: &'a Repository, remote_name: &str) -> Result<Commit<'a>, Error> {
+    if let Ok(mut stale) = repo.find_reference(FETCH_REF) {
+        stale.delete()?;
+    }
+    // libgit2 also applies a named remote's configured refspecs, and the push
+    // refspec `st remote setup` adds would make it overwrite the local
+    // snapshot ref; fetch through an anonymous remote with the same URL
+    let mut git_remote = match repo.find_remote(remote_name) {
+        Ok(named) => repo.remote_anonymous(&String::from_utf8_lossy(named.url_bytes()))?,
+        Err(_) => storage::find_remote(repo, remote_name)?,
+    };
+    git_remote.fetch(
+        &[&format!("+{}:{FETCH_REF}", snapshot::SNAPSHOT_REF)],
+        None,
+        None,
+    )?;
+
+    // A missing source ref is not an error for git, it just fetches nothing
+    let Ok(mut fetched) = repo.find_reference(FETCH_REF) else {
+        return Err(Error::RemoteNotInitialized(remote_name.to_string()));
+    };
+    let commit = fetched.peel_to_commit()?;
+    fetched.delete()?;
+
+    if repo.find_remote(remote_name).is_ok() {
+        repo.reference(
+            &snapshot::remote_ref(remote_name),
+            commit.id(),
+            true,
+            &format!("st fetch {remote_name}"),
+        )?;
+    }
+    Ok(commit)
+}
+
+pub fn run(remote: Option<String>) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let remote_name = remote.unwrap_or_else(|| "origin".to_string());
+
+    let previous = snapshot::remote_commit(&repo, &remote_name).map(|c| c.id());
+    let commit = fetch(&repo, &remote_name)?;
+
+    let id = commit.id().to_string();
+    if previous == Some(commit.id()) {
+        eprintln!("{remote_name} is unchanged at {}", &id[..7]);
+    } else {
+        eprintln!("Fetched {remote_name} snapshot {}", &id[..7]);
+    }
+    if snapshot::is_initialized(&repo) {
+        let local = snapshot::get_snapshot_commit(&repo)?;
+        let (ahead, behind) = repo.graph_ahead_behind(local.id(), commit.id())?;
+        eprintln!("Local snapshot is {ahead} commits ahead, {behind} behind {remote_name}");
+    }
+    Ok(())
+}
@@ -0,0 +1,562 @@
+use std::collections::HashSet;
+
+use git2::{Repository, Tree};
+use serde::Serialize;
+
+use crate::dag;
+use crate::error::Error;
+use crate::snapshot::{self, Changeset};
+use crate::storage;
+use crate::types::{Conflict, Edge, Issue, Status};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    MissingSubtree,
+    CorruptedBlob,
+    SchemaMismatch,
+    IdMismatch,
+    EdgeNameMismatch,
+    ConflictNameMismatch,
+    DanglingEdge,
+    ClaimMismatch,
+    Cycle,
+    StaleMeta,
+    DescriptionConflict,
+}
+
+/// A single integrity problem in the snapshot tree
+#[derive(Serialize, Debug, Clone)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    /// Tree path of the offending entry, e.g. `issues/st-a3f8`
+    pub path: String,
+    pub message: String,
+    pub fixable: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct FsckReport {
+    pub problems: Vec<Problem>,
+    pub repaired: bool,
+}
+
+impl FsckReport {
+    fn push(&mut self, kind: ProblemKind, path: String, message: String, fixable: bool) {
+        self.problems.push(Problem {
+            kind,
+            path,
+            message,
+            fixable,
+        });
+    }
+}
+
+/// Validate the whole snapshot tree and report every problem found.
+/// With `repair`, all fixable problems are fixed in a single commit.
+pub fn fsck(repo: &Repository, repair: bool) -> Result<FsckReport, Error> {
+    if !snapshot::is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let commit = snapshot::get_snapshot_commit(repo)?;
+    let tree = commit.tree()?;
+
+    let mut report = FsckReport::default();
+    let mut changes = Changeset::default();
+
+    let issues_tree = subtree(repo, &tree, "issues", &mut report);
+    let edges_tree = subtree(repo, &tree, "edges", &mut report);
+
+    let editor = storage::get_editor(repo)?;
+    // Highest clock in the tree, so repairs dominate everything already written
+    let stored_meta = snapshot::stored_meta(repo, &tree);
+    let rebuilt_meta = snapshot::rebuild_meta(repo, &tree).ok();
+    let clock = [&stored_meta, &rebuilt_meta]
+        .into_iter()
+        .flatten()
+        .map(|m| m.lamport)
+        .max()
+        .unwrap_or(0);
+
+    // Every entry name counts as an existing issue, even if its blob is broken
+    let mut issue_ids: HashSet<String> = HashSet::new();
+    let mut deleted_ids: HashSet<String> = HashSet::new();
+
+    if let Some(ref issues_tree) = issues_tree {
+        for entry in issues_tree.iter() {
+            let name = entry.name().unwrap_or("").to_string();
+            let path = format!("issues/{name}");
+            issue_ids.insert(name.clone());
+
+            let Some(mut issue) = decode(repo, &entry, &path, Issue::from_json, &mut report) else {
+                continue;
+            };
+            if issue.deleted {
+                deleted_ids.insert(issue.id.clone());
+            }
+            let decoded = issue.clone();
+            let mut dirty = false;
+
+            // An issue can only be rewritten if its id does not clash with another entry
+            let existing = issues_tree.get_name(&issue.id).map(|e| e.id());
+            let writable = issue.id == name || existing.is_none();
+            if issue.id != name {
+                // A byte-identical copy under the wrong name can simply be dropped
+                let duplicate = existing == Some(entry.id());
+                report.push(
+                    ProblemKind::IdMismatch,
+                    path.clone(),
+                    format!("entry name does not match issue id {}", issue.id),
+                    writable || duplicate,
+                );
+                if duplicate {
+                    changes.removed_issues.push(name.clone());
+                    continue;
+                }
+                if writable {
+                    changes.removed_issues.push(name.clone());
+                    issue_ids.insert(issue.id.clone());
+                    dirty = true;
+                }
+            }
+
+            if issue.conflict && !issue.deleted {
+                report.push(
+                    ProblemKind::DescriptionConflict,
+                    path.clone(),
+                    "description has unresolved merge conflicts; rewrite it with st update -d"
+                        .to_string(),
+                    false,
+                );
+            }
+
+            for message in fix_claim_state(&mut issue) {
+                report.push(ProblemKind::ClaimMismatch, path.clone(), message, writable);
+                dirty = writable;
+            }
+
+            if dirty {
+                issue.record_edit(&decoded, editor.clone(), clock);
+                changes.issues.push(issue);
+            }
+        }
+    }
+
+    // Edges that survive the per-entry checks, keyed by their entry name
+    let mut graph: Vec<(String, Edge)> = Vec::new();
+
+    if let Some(ref edges_tree) = edges_tree {
+        for entry in edges_tree.iter() {
+            let name = entry.name().unwrap_or("").to_string();
+            let path = format!("edges/{name}");
+
+            let Some(edge) = decode(repo, &entry, &path, Edge::from_json, &mut report) else {
+                continue;
+            };
+            let expected = snapshot::edge_name(&edge);
+            if name != expected {
+                let fixable = edges_tree.get_name(&expected).is_none();
+                report.push(
+                    ProblemKind::EdgeNameMismatch,
+                    path,
+                    format!("entry name does not match payload {expected}"),
+                    fixable,
+                );
+                if fixable {
+                    changes.removed_edges.push(name.clone());
+                    changes.edges.push(edge.clone());
+                }
+            }
+            if edge.is_live() {
+                graph.push((name, edge));
+            }
+        }
+    }
+
+    // Quarantine edges to missing or deleted issues and one edge per cycle,
+    // picked the way pull picks them, so they stay around for review. A
+    // tombstone stays valid after its issues are gone.
+    let live_ids: HashSet<String> = issue_ids.difference(&deleted_ids).cloned().collect();
+    let edges: Vec<Edge> = graph.iter().map(|(_, e)| e.clone()).collect();
+    for (index, reason) in dag::invalid_edges(&live_ids, &edges) {
+        let (name, edge) = &graph[index];
+        let dangling = [&edge.source, &edge.target]
+            .into_iter()
+            .any(|id| !live_ids.contains(id));
+        let kind = if dangling {
+            ProblemKind::DanglingEdge
+        } else {
+            ProblemKind::Cycle
+        };
+        report.push(kind, format!("edges/{name}"), reason.clone(), true);
+
+        let expected = snapshot::edge_name(edge);
+        changes.edges.retain(|e| snapshot::edge_name(e) != expected);
+        if *name != expected && !changes.removed_edges.contains(name) {
+            changes.removed_edges.push(name.clone());
+        }
+        let mut edge = edge.clone();
+        edge.quarantine = Some(reason);
+        edge.record_edit(editor.clone(), clock);
+        changes.edges.push(edge);
+    }
+
+    // A conflict record that cannot be read would make every pull fail
+    let conflicts_tree = match snapshot::get_optional_subtree(repo, &tree, "conflicts") {
+        Ok(conflicts_tree) => conflicts_tree,
+        Err(e) => {
+            report.push(
+                ProblemKind::MissingSubtree,
+                "conflicts".to_string(),
+                e.to_string(),
+                false,
+            );
+            None
+        }
+    };
+    if let Some(ref conflicts_tree) = conflicts_tree {
+        for entry in conflicts_tree.iter() {
+            let name = entry.name().unwrap_or("").to_string();
+            let path = format!("conflicts/{name}");
+
+            let Some(conflict) = decode(repo, &entry, &path, Conflict::from_json, &mut report)
+            else {
+                continue;
+            };
+            let expected = snapshot::conflict_name(&conflict);
+            if name != expected {
+                let fixable = conflicts_tree.get_name(&expected).is_none();
+                report.push(
+                    ProblemKind::ConflictNameMismatch,
+                    path,
+                    format!("entry name does not match record {expected}"),
+                    fixable,
+                );
+                if fixable {
+                    changes.removed_conflicts.push(name);
+                    changes.conflicts.push(conflict);
+                }
+            }
+        }
+    }
+
+    // The meta blob must list exactly the current entries and carry the highest clock
+    if let (Some(issues_tree), Some(edges_tree)) = (&issues_tree, &edges_tree) {
+        match (&stored_meta, &rebuilt_meta) {
+            (None, _) => report.push(
+                ProblemKind::StaleMeta,
+                "meta".to_string(),
+                "missing or unreadable snapshot metadata".to_string(),
+                true,
+            ),
+            (Some(meta), Some(rebuilt)) => {
+                let expected = snapshot::build_meta(
+                    meta,
+                    issues_tree,
+                    edges_tree,
+                    conflicts_tree.as_ref(),
+                    meta.lamport,
+                );
+                if !meta.same_content(&expected) {
+                    report.push(
+                        ProblemKind::StaleMeta,
+                        "meta".to_string(),
+                        "manifest does not match snapshot contents".to_string(),
+                        true,
+                    );
+                }
+                if meta.lamport < rebuilt.lamport {
+                    report.push(
+                        ProblemKind::StaleMeta,
+                        "meta".to_string(),
+                        format!(
+                            "repo clock {} is behind highest clock {}",
+                            meta.lamport, rebuilt.lamport
+                        ),
+                        true,
+                    );
+                }
+            }
+            (Some(_), None) => {}
+        }
+        changes.witness = clock;
+    }
+
+    if repair && report.problems.iter().any(|p| p.fixable) {
+        let fixed = report.problems.iter().filter(|p| p.fixable).count();
+        snapshot::commit_changes(repo, &changes, &format!("Fsck repair: {fixed} problems"))?;
+        report.repaired = true;
+    }
+
+    Ok(report)
+}
+
+fn subtree<'a>(
+    repo: &'a Repository,
+    tree: &Tree,
+    name: &str,
+    report: &mut FsckReport,
+) -> Option<Tree<'a>> {
+    match snapshot::get_subtree(repo, tree, name) {
+        Ok(subtree) => Some(subtree),
+        Err(e) => {
+            report.push(
+                ProblemKind::MissingSubtree,
+                name.to_string(),
+                e.to_string(),
+                false,
+            );
+            None
+        }
+    }
+}
+
+/// Decode a blob entry, recording a problem instead of failing
+fn decode<T>(
+    repo: &Repository,
+    entry: &git2::TreeEntry,
+    path: &str,
+    parse: fn(&[u8]) -> Result<T, Error>,
+    report: &mut FsckReport,
+) -> Option<T> {
+    let blob = match entry.to_object(repo).and_then(|obj| obj.peel_to_blob()) {
+        Ok(blob) => blob,
+        Err(e) => {
+            report.push(
+                ProblemKind::CorruptedBlob,
+                path.to_string(),
+                e.to_string(),
+                false,
+            );
+            return None;
+        }
+    };
+    match parse(blob.content()) {
+        Ok(value) => Some(value),
+        Err(e @ (Error::SchemaMismatch { .. } | Error::SchemaTooNew { .. })) => {
+            report.push(
+                ProblemKind::SchemaMismatch,
+                path.to_string(),
+                e.to_string(),
+                false,
+            );
+            None
+        }
+        Err(e) => {
+            report.push(
+                ProblemKind::CorruptedBlob,
+                path.to_string(),
+                e.to_string(),
+                false,
+            );
+            None
+        }
+    }
+}
+
+/// Bring status and claim fields back in line, returning what was wrong.
+/// Status and claimed move together: only in_progress issues are claimed.
+fn fix_claim_state(issue: &mut Issue) -> Vec<String> {
+    let mut problems = Vec::new();
+    match (issue.status, issue.claimed) {
+        (Status::InProgress, false) => {
+            problems.push("in_progress but not claimed".to_string());
+            issue.status = Status::Open;
+        }
+        (Status::Open, true) => {
+            problems.push("claimed but open".to_string());
+            issue.status = Status::InProgress;
+        }
+        (Status::Closed, true) => {
+            problems.push("closed but still claimed".to_string());
+            issue.claimed = false;
+        }
+        _ => {}
+    }
+    if !issue.claimed
+        && (issue.claimed_by.is_some()
+            || issue.claim_context.is_some()
+            || issue.claimed_at.is_some())
+    {
+        problems.push("claim context set on unclaimed issue".to_string());
+        issue.claimed_by = None;
+        issue.claim_context = None;
+        issue.claimed_at = None;
+    }
+    problems
+}
+
+pub fn run(repair: bool, json: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let report = fsck(&repo, repair)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for problem in &report.problems {
+            let note = match (problem.fixable, report.repaired) {
+                (true, true) => " (repaired)",
+                (true, false) => " (fixable)",
+                (false, _) => "",
+            };
+            println!("{}: {}{}", problem.path, problem.message, note);
+        }
+    }
+
+    let remaining = report
+        .problems
+        .iter()
+        .filter(|p| !(p.fixable && report.repaired))
+        .count();
+    if remaining > 0 {
+        return Err(Error::FsckFailed(remaining));
+    }
+    if !json {
+        if report.problems.is_empty() {
+            println!("No problems found");
+        } else {
+            println!("Repaired {} problems", report.problems.len());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{create_issue, head, make_edge, repo};
+    use crate::types::EdgeType;
+
+    /// Commit raw blobs over the snapshot, bypassing every check on the way in.
+    /// Each entry is `(subtree, name, content)`; no content removes the entry.
+    fn write_raw(repo: &Repository, entries: &[(&str, &str, Option<Vec<u8>>)]) {
+        let parent = head(repo);
+        let root = parent.tree().unwrap();
+        let mut root_builder = repo.treebuilder(Some(&root)).unwrap();
+        for dir in ["issues", "edges"] {
+            let subtree = snapshot::get_subtree(repo, &root, dir).unwrap();
+            let mut builder = repo.treebuilder(Some(&subtree)).unwrap();
+            for (_, name, content) in entries.iter().filter(|(d, _, _)| *d == dir) {
+                match content {
+                    Some(content) => {
+                        let blob = repo.blob(content).unwrap();
+                        builder.insert(name, blob, 0o100644).unwrap();
+                    }
+                    None => builder.remove(name).unwrap(),
+                }
+            }
+            root_builder
+                .insert(dir, builder.write().unwrap(), 0o040000)
+                .unwrap();
+        }
+        let tree = repo.find_tree(root_builder.write().unwrap()).unwrap();
+        let sig = repo.signature().unwrap();
+        repo.commit(
+            Some(snapshot::SNAPSHOT_REF),
+            &sig,
+            &sig,
+            "Corrupt",
+            &tree,
+            &[&parent],
+        )
+        .unwrap();
+    }
+
+    fn json<T: Serialize>(value: &T) -> Option<Vec<u8>> {
+        Some(serde_json::to_vec(value).unwrap())
+    }
+
+    fn kinds(report: &FsckReport) -> Vec<ProblemKind> {
+        report.problems.iter().map(|p| p.kind).collect()
+    }
+
+    #[test]
+    fn test_clean_snapshot_has_no_problems() {
+        let (_dir, repo) = repo();
+        let a = create_issue(&repo, "A");
+        let b = create_issue(&repo, "B");
+        crate::commands::dep::add_edge(&repo, &a.id, &b.id, EdgeType::DependsOn).unwrap();
+
+        assert!(fsck(&repo, false).unwrap().problems.is_empty());
+    }
+
+    #[test]
+    fn test_reports_every_problem_and_repairs_fixable_ones_in_one_commit() {
+        let (_dir, repo) = repo();
+        let renamed = create_issue(&repo, "Renamed");
+        let mut unclaimed = create_issue(&repo, "Unclaimed");
+        let (x, y) = (create_issue(&repo, "X"), create_issue(&repo, "Y"));
+
+        unclaimed.status = Status::InProgress;
+        let mut future = serde_json::to_value(create_issue(&repo, "Future")).unwrap();
+        future["schema_version"] = 999.into();
+        let dangling = make_edge(&x.id, "st-gone", EdgeType::DependsOn);
+        let misnamed = make_edge(&x.id, &renamed.id, EdgeType::RelatesTo);
+        let (xy, yx) = (
+            make_edge(&x.id, &y.id, EdgeType::DependsOn),
+            make_edge(&y.id, &x.id, EdgeType::DependsOn),
+        );
+        write_raw(
+            &repo,
+            &[
+                ("issues", &renamed.id, None),
+                ("issues", "st-wrong", json(&renamed)),
+                ("issues", &unclaimed.id, json(&unclaimed)),
+                ("issues", "st-future", json(&future)),
+                ("issues", "st-junk", Some(b"not json".to_vec())),
+                ("edges", &snapshot::edge_name(&dangling), json(&dangling)),
+                ("edges", "misnamed", json(&misnamed)),
+                ("edges", &snapshot::edge_name(&xy), json(&xy)),
+                ("edges", &snapshot::edge_name(&yx), json(&yx)),
+            ],
+        );
+
+        let report = fsck(&repo, false).unwrap();
+        let found = kinds(&report);
+        for kind in [
+            ProblemKind::IdMismatch,
+            ProblemKind::SchemaMismatch,
+            ProblemKind::CorruptedBlob,
+            ProblemKind::ClaimMismatch,
+            ProblemKind::DanglingEdge,
+            ProblemKind::EdgeNameMismatch,
+            ProblemKind::Cycle,
+            ProblemKind::StaleMeta,
+        ] {
+            assert!(found.contains(&kind), "{kind:?} missing from {found:?}");
+        }
+        assert!(!report.repaired);
+
+        let before = head(&repo).id();
+        let report = fsck(&repo, true).unwrap();
+        assert!(report.repaired);
+        let after = head(&repo);
+        assert_eq!(after.parent_ids().collect::<Vec<_>>(), vec![before]);
+
+        // Only the problems fsck cannot fix are left
+        let left = fsck(&repo, false).unwrap();
+        assert!(left.problems.iter().all(|p| !p.fixable));
+        let mut left = kinds(&left);
+        left.sort_by_key(|k| format!("{k:?}"));
+        assert_eq!(
+            left,
+            vec![ProblemKind::CorruptedBlob, ProblemKind::SchemaMismatch]
+        );
+
+        // The unreadable entries still block loading the whole tree
+        let tree = after.tree().unwrap();
+        let issue = |id: &str| {
+            let oid = snapshot::issue_blob_id(&tree, id).unwrap();
+            snapshot::read_issue_blob(&repo, oid).unwrap()
+        };
+        assert_eq!(issue(&renamed.id).title, "Renamed");
+        assert!(snapshot::issue_blob_id(&tree, "st-wrong").is_none());
+        assert_eq!(issue(&unclaimed.id).status, Status::Open);
+        let edges = snapshot::load_all_edges(&repo).unwrap();
+        let quarantined = edges.iter().filter(|e| e.quarantine.is_some()).count();
+        assert_eq!(quarantined, 2);
+        assert!(edges.iter().any(|e| e.same_key(&misnamed)));
+    }
+}
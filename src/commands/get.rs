@@ -1,14 +1,25 @@
 use chrono::{TimeZone, Utc};
-use git2::Repository;
+use git2::{Commit, Repository};
 
 use crate::error::Error;
 use crate::snapshot;
 use crate::types::Issue;
 
-pub fn run(id_prefix: String, json: bool) -> Result<(), Error> {
+/// Look up a single issue by ID or unique prefix, optionally as of `at`
+pub fn get(repo: &Repository, at: Option<&Commit>, id_prefix: &str) -> Result<Issue, Error> {
+    match at {
+        Some(commit) => snapshot::load_issue_at(repo, commit, id_prefix),
+        None => snapshot::load_issue(repo, id_prefix),
+    }
+}
+
+pub fn run(at: Option<String>, id_prefix: String, json: bool) -> Result<(), Error> {
     let repo = Repository::discover(".")?;
+    let at = at
+        .map(|spec| snapshot::resolve_at(&repo, &spec))
+        .transpose()?;
 
-    let issue = snapshot::load_issue(&repo, &id_prefix)?;
+    let issue = get(&repo, at.as_ref(), &id_prefix)?;
 
     if json {
         println!("{}", serde_json::to_string_pretty(&issue)?);
@@ -31,18 +42,24 @@ fn print_issue(issue: &Issue) {
     println!("Updated:     {}", format_timestamp(issue.updated_at));
     println!("Editor:      {}", issue.editor);
     println!("Claimed:     {}", issue.claimed);
+    if let Some(ref by) = issue.claimed_by {
+        println!("Claimed by:  {by}");
+    }
     if let Some(ref ctx) = issue.claim_context {
         println!("Context:     {ctx}");
     }
     if let Some(ref reason) = issue.reason {
         println!("Reason:      {reason}");
     }
+    if issue.conflict {
+        println!("Conflict:    description has unresolved merge conflicts");
+    }
     if !issue.description.is_empty() {
         println!("\n{}", issue.description);
     }
 }
 
-fn format_timestamp(ts: i64) -> String {
+pub(crate) fn format_timestamp(ts: i64) -> String {
     Utc.timestamp_opt(ts, 0)
         .single()
         .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
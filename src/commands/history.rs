@@ -0,0 +1,162 @@
+use git2::Repository;
+use serde::Serialize;
+
+use crate::commands::get::format_timestamp;
+use crate::error::Error;
+use crate::snapshot;
+use crate::types::FieldChange;
+
+/// One snapshot commit that changed the issue
+#[derive(Serialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub commit: String,
+    pub message: String,
+    pub timestamp: i64,
+    pub editor: String,
+    pub lamport: u64,
+    pub created: bool,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Walk snapshot commits oldest first and record every change to an issue,
+/// which may since have been deleted
+pub fn history(repo: &Repository, id_prefix: &str) -> Result<Vec<HistoryEntry>, Error> {
+    let id = snapshot::find_any_issue_id(repo, id_prefix)?;
+
+    let mut entries = Vec::new();
+    let mut previous = None;
+    let mut previous_oid = None;
+
+    for commit in snapshot::history(repo)? {
+        let tree = commit.tree()?;
+        let oid = snapshot::issue_blob_id(&tree, &id);
+        if oid == previous_oid {
+            continue;
+        }
+        previous_oid = oid;
+
+        let Some(oid) = oid else {
+            previous = None;
+            continue;
+        };
+        let issue = snapshot::read_issue_blob(repo, oid)?;
+
+        let changes = match previous {
+            Some(ref old) => issue.changes_from(old),
+            None => Vec::new(),
+        };
+
+        entries.push(HistoryEntry {
+            commit: commit.id().to_string(),
+            message: commit.summary().unwrap_or("").to_string(),
+            timestamp: issue.updated_at,
+            editor: issue.editor.clone(),
+            lamport: issue.lamport,
+            created: previous.is_none(),
+            changes,
+        });
+        previous = Some(issue);
+    }
+
+    Ok(entries)
+}
+
+pub fn run(id_prefix: String, json: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let entries = history(&repo, &id_prefix)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    for entry in entries {
+        println!(
+            "{}  {}  lamport {}  ({}) {}",
+            format_timestamp(entry.timestamp),
+            entry.editor,
+            entry.lamport,
+            &entry.commit[..7],
+            entry.message
+        );
+        if entry.created {
+            println!("    created");
+        }
+        for change in entry.changes {
+            if change.field == "description" {
+                println!("    description changed");
+            } else {
+                println!("    {}: {:?} -> {:?}", change.field, change.from, change.to);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{claim, close, delete, update};
+    use crate::testing::{create_issue, repo};
+
+    #[test]
+    fn test_history_lists_changes_oldest_first() {
+        let (_dir, repo) = repo();
+        let issue = create_issue(&repo, "First");
+        let other = create_issue(&repo, "Other");
+        let changes = update::Changes {
+            title: Some("Second".to_string()),
+            ..Default::default()
+        };
+        update::update(&repo, &issue.id, changes).unwrap();
+        claim::claim(&repo, &issue.id, Some("fix/it".to_string())).unwrap();
+        close::close(&repo, &other.id, None).unwrap();
+
+        // The commit that only touched the other issue is skipped
+        let entries = history(&repo, &issue.id).unwrap();
+        let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                format!("Create issue {}", issue.id),
+                format!("Update issue {}", issue.id),
+                format!("Claim issue {}", issue.id),
+            ]
+        );
+        assert!(entries[0].created && entries[0].changes.is_empty());
+        assert!(entries.windows(2).all(|w| w[0].lamport < w[1].lamport));
+        assert_eq!(entries[1].changes.len(), 1);
+        assert_eq!(entries[1].changes[0].field, "title");
+        assert_eq!(entries[1].changes[0].from, "First");
+        assert_eq!(entries[1].changes[0].to, "Second");
+        let claimed: Vec<&str> = entries[2]
+            .changes
+            .iter()
+            .map(|c| c.field.as_str())
+            .collect();
+        assert!(claimed.contains(&"status") && claimed.contains(&"claimed"));
+        assert!(entries.iter().all(|e| e.editor == "a@x"));
+    }
+
+    #[test]
+    fn test_history_of_deleted_issue() {
+        let (_dir, repo) = repo();
+        let issue = create_issue(&repo, "Doomed");
+        close::close(&repo, &issue.id, Some("done".to_string())).unwrap();
+        delete::delete(&repo, &issue.id[..5]).unwrap();
+
+        let entries = history(&repo, &issue.id[..5]).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].created);
+        let fields = |i: usize| -> Vec<&str> {
+            entries[i]
+                .changes
+                .iter()
+                .map(|c| c.field.as_str())
+                .collect()
+        };
+        assert!(fields(1).contains(&"status"));
+        assert_eq!(fields(2), vec!["deleted"]);
+    }
+}
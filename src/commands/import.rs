@@ -1,119 +1,266 @@
 use std::fs;
 
 use git2::Repository;
-use serde::Deserialize;
+use serde::Serialize;
 
+use crate::commands::diff::{self, DiffReport};
+use crate::commands::export::Export;
+use crate::commands::pull;
 use crate::dag;
 use crate::error::Error;
-use crate::snapshot;
-use crate::types::{Edge, EdgeType, Issue};
-
-#[derive(Deserialize)]
-struct Import {
-    #[allow(dead_code)]
-    version: u32,
-    #[allow(dead_code)]
-    exported_at: i64,
-    issues: Vec<Issue>,
-    edges: Vec<Edge>,
-}
+use crate::merge;
+use crate::snapshot::{self, Changeset};
+use crate::storage;
+use crate::types::{Conflict, Edge, EdgeType, Issue};
 
-pub fn run(file: String) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
+/// Outcome of merging an export into the snapshot
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub issues_added: usize,
+    pub issues_updated: usize,
+    pub issues_deleted: usize,
+    pub edges_added: usize,
+    pub edges_removed: usize,
+    /// Edges left out, and why
+    pub edges_skipped: Vec<SkippedEdge>,
+    /// Edges set aside because an issue they reference is missing or deleted
+    pub edges_quarantined: Vec<Edge>,
+    /// Versions the merge discarded, recorded under `conflicts/`
+    pub conflicts: Vec<Conflict>,
+    /// Live issues and edges before and after the import, field by field
+    pub changes: DiffReport,
+}
 
-    let existing_issues = snapshot::load_issues(&repo)?;
-    let existing_edges = snapshot::load_edges(&repo)?;
+/// An imported edge that was not written
+#[derive(Serialize, Debug, Clone)]
+pub struct SkippedEdge {
+    #[serde(flatten)]
+    pub edge: Edge,
+    pub reason: String,
+}
 
-    let content = fs::read_to_string(&file)?;
-    let import: Import = serde_json::from_str(&content)?;
+/// Merge an export into the snapshot in a single commit. With `dry_run`
+/// nothing is written, but the summary is the same.
+pub fn import(repo: &Repository, import: Export, dry_run: bool) -> Result<ImportSummary, Error> {
+    let existing_issues = snapshot::load_all_issues(repo)?;
+    let existing_edges = snapshot::load_all_edges(repo)?;
 
     let mut issues_to_save: Vec<Issue> = Vec::new();
-    let mut edges_to_add: Vec<Edge> = Vec::new();
-    let mut edges_skipped = 0;
+    let mut edges_to_save: Vec<Edge> = Vec::new();
+    let mut edges_skipped: Vec<SkippedEdge> = Vec::new();
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    let editor = storage::get_editor(repo)?;
+    let conflict_clock = snapshot::clock(repo)? + 1;
 
-    // Collect issues (LWW by Lamport)
+    // Collect issues (field-level merge). An export carries no common base,
+    // so every differing field the merge drops is recorded as a conflict.
     for imported_issue in import.issues {
-        let dominated = if let Some(existing) = existing_issues.get(&imported_issue.id) {
-            imported_issue.lamport > existing.lamport
-                || (imported_issue.lamport == existing.lamport
-                    && imported_issue.updated_at > existing.updated_at)
-        } else {
-            true
+        let merged = match existing_issues.get(&imported_issue.id) {
+            Some(existing) => {
+                let merged = merge::merge_issue(existing, &imported_issue);
+                let fields = merge::discarded_fields(existing, &imported_issue, &merged, None);
+                if !fields.is_empty() {
+                    conflicts.push(Conflict::record(
+                        existing,
+                        &imported_issue,
+                        fields,
+                        "import",
+                        &editor,
+                        conflict_clock,
+                    ));
+                }
+                merged
+            }
+            None => imported_issue,
         };
 
-        if dominated {
-            issues_to_save.push(imported_issue);
+        if existing_issues.get(&merged.id) != Some(&merged) {
+            issues_to_save.push(merged);
         }
     }
 
-    // Collect edges (union - skip duplicates and cycles)
-    let mut current_edges = existing_edges.clone();
+    // Collect edges (tombstones included - skip cycles)
+    let mut current_edges: Vec<Edge> = existing_edges
+        .iter()
+        .filter(|e| e.is_live())
+        .cloned()
+        .collect();
 
     for imported_edge in import.edges {
-        let exists = current_edges.iter().any(|e| {
-            e.source == imported_edge.source
-                && e.target == imported_edge.target
-                && e.edge_type == imported_edge.edge_type
-        });
+        let existing = existing_edges.iter().find(|e| e.same_key(&imported_edge));
+        let imported_edge = match existing {
+            Some(e) => merge::merge_edge(e, &imported_edge),
+            None => imported_edge,
+        };
+        if existing == Some(&imported_edge) {
+            continue;
+        }
+        let was_live = existing.is_some_and(Edge::is_live);
 
-        if exists {
+        if !imported_edge.is_live() {
+            current_edges.retain(|e| !e.same_key(&imported_edge));
+            edges_to_save.push(imported_edge);
             continue;
         }
 
         // Check for cycles (skip for RelatesTo and Duplicates)
-        if !matches!(
-            imported_edge.edge_type,
-            EdgeType::RelatesTo | EdgeType::Duplicates
-        )
+        if !was_live
+            && !matches!(
+                imported_edge.edge_type,
+                EdgeType::RelatesTo | EdgeType::Duplicates
+            )
             && dag::would_create_cycle(
                 &current_edges,
                 &imported_edge.source,
                 &imported_edge.target,
                 imported_edge.edge_type,
-            ) {
-                eprintln!(
-                    "Skipping edge {} -> {} ({}): would create cycle",
-                    imported_edge.source,
-                    imported_edge.target,
-                    imported_edge.edge_type.as_str()
-                );
-                edges_skipped += 1;
-                continue;
-            }
+            )
+        {
+            edges_skipped.push(SkippedEdge {
+                edge: imported_edge,
+                reason: "would create cycle".to_string(),
+            });
+            continue;
+        }
 
-        current_edges.push(imported_edge.clone());
-        edges_to_add.push(imported_edge);
+        if !was_live {
+            current_edges.push(imported_edge.clone());
+        }
+        edges_to_save.push(imported_edge);
+    }
+
+    // The import may delete issues that live edges, imported or local, still
+    // point at; set those aside the way pull does
+    let edges_quarantined = pull::quarantine_edges(
+        &existing_issues,
+        &issues_to_save,
+        &existing_edges,
+        &mut edges_to_save,
+        &editor,
+        snapshot::clock(repo)?,
+    );
+
+    let mut edges_added = 0;
+    let mut edges_removed = 0;
+    for edge in &edges_to_save {
+        let existing = existing_edges.iter().find(|e| e.same_key(edge));
+        match (existing.is_some_and(Edge::is_live), edge.is_live()) {
+            (false, true) => edges_added += 1,
+            (true, false) => edges_removed += 1,
+            _ => {}
+        }
     }
 
     // Calculate counts
     let issues_added = issues_to_save
         .iter()
-        .filter(|i| !existing_issues.contains_key(&i.id))
+        .filter(|i| !i.deleted && !existing_issues.contains_key(&i.id))
+        .count();
+    // A tombstone for an issue never seen here is a deletion, not an addition
+    let issues_deleted = issues_to_save
+        .iter()
+        .filter(|i| i.deleted && existing_issues.get(&i.id).is_none_or(|e| !e.deleted))
         .count();
-    let issues_updated = issues_to_save.len() - issues_added;
-    let edges_added = edges_to_add.len();
+    let issues_updated = issues_to_save.len() - issues_added - issues_deleted;
+
+    let before = diff::resolve(repo, None, false)?;
+    let after = before.with_changes("after import".to_string(), &issues_to_save, &edges_to_save);
+    let changes = diff::diff(&before, &after);
 
     // Single batch commit
-    if !issues_to_save.is_empty() || !edges_to_add.is_empty() {
-        snapshot::merge_snapshot(
-            &repo,
-            &issues_to_save,
-            &edges_to_add,
+    let batch = Changeset {
+        issues: issues_to_save,
+        edges: edges_to_save,
+        conflicts: conflicts.clone(),
+        ..Default::default()
+    };
+    if !dry_run && !batch.is_empty() {
+        snapshot::commit_changes(
+            repo,
+            &batch,
             &format!(
                 "Import: {} issues, {} edges",
-                issues_to_save.len(),
-                edges_to_add.len()
+                batch.issues.len(),
+                batch.edges.len()
             ),
         )?;
     }
 
-    println!(
-        "Imported: {} issues added, {} issues updated, {} edges added{}",
+    Ok(ImportSummary {
         issues_added,
         issues_updated,
+        issues_deleted,
         edges_added,
+        edges_removed,
+        edges_skipped,
+        edges_quarantined,
+        conflicts,
+        changes,
+    })
+}
+
+pub fn run(file: String, dry_run: bool, json: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let content = fs::read(&file)?;
+    let data = Export::from_json(&content)?;
+
+    let summary = import(&repo, data, dry_run)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    let skipping = if dry_run { "Would skip" } else { "Skipping" };
+    for skipped in &summary.edges_skipped {
+        eprintln!(
+            "{skipping} edge {} -> {} ({}): {}",
+            skipped.edge.source,
+            skipped.edge.target,
+            skipped.edge.edge_type.as_str(),
+            skipped.reason
+        );
+    }
+
+    let quarantined = if dry_run {
+        "Would quarantine"
+    } else {
+        "Quarantined"
+    };
+    for edge in &summary.edges_quarantined {
+        println!(
+            "{quarantined} edge {} {} {}: {}",
+            edge.source,
+            edge.edge_type.as_str(),
+            edge.target,
+            edge.quarantine.as_deref().unwrap_or("")
+        );
+    }
+    let recorded = if dry_run { "Would record" } else { "Recorded" };
+    for conflict in &summary.conflicts {
+        println!(
+            "{recorded} conflict on {} ({}): {}",
+            conflict.id,
+            conflict.title,
+            conflict.fields.join(", ")
+        );
+    }
+
+    if dry_run {
+        diff::print_changes(&summary.changes);
+    }
+    let edges_skipped = summary.edges_skipped.len();
+    println!(
+        "{}: {} issues added, {} issues updated, {} issues deleted, {} edges added, {} edges removed{}",
+        if dry_run { "Would import" } else { "Imported" },
+        summary.issues_added,
+        summary.issues_updated,
+        summary.issues_deleted,
+        summary.edges_added,
+        summary.edges_removed,
         if edges_skipped > 0 {
-            format!(" ({edges_skipped} edges skipped due to cycles)")
+            format!(" ({edges_skipped} edges skipped)")
         } else {
             String::new()
         }
@@ -121,3 +268,28 @@ pub fn run(file: String) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{delete, export};
+    use crate::testing::{create_issue, repo};
+
+    #[test]
+    fn test_unknown_tombstone_counts_as_deleted() {
+        let (_dir, source) = repo();
+        let kept = create_issue(&source, "Kept");
+        let gone = create_issue(&source, "Gone");
+        delete::delete(&source, &gone.id).unwrap();
+        let export = export::export(&source, None).unwrap();
+
+        let (_dir, target) = repo();
+        let summary = import(&target, export, false).unwrap();
+        assert_eq!(summary.issues_added, 1);
+        assert_eq!(summary.issues_deleted, 1);
+        assert_eq!(summary.issues_updated, 0);
+        let issues = snapshot::load_all_issues(&target).unwrap();
+        assert!(!issues[&kept.id].deleted);
+        assert!(issues[&gone.id].deleted);
+    }
+}
@@ -1,13 +1,77 @@
-use git2::Repository;
+use git2::{Commit, Repository};
 
+use crate::commands::fetch;
 use crate::error::Error;
 use crate::snapshot;
 
-pub fn run() -> Result<(), Error> {
+/// Initialize by adopting a remote's snapshot instead of creating a new root,
+/// so the first pull and push are fast-forwards. Returns the adopted commit.
+pub fn init_from<'a>(repo: &'a Repository, remote_name: &str) -> Result<Commit<'a>, Error> {
+    if snapshot::is_initialized(repo) {
+        return Err(Error::AlreadyInitialized);
+    }
+    let commit = fetch::fetch(repo, remote_name)?;
+    snapshot::adopt(repo, &commit)?;
+    Ok(commit)
+}
+
+pub fn run(from: Option<String>) -> Result<(), Error> {
     let repo = Repository::discover(".")?;
 
-    snapshot::init(&repo)?;
+    let Some(remote_name) = from else {
+        snapshot::init(&repo)?;
+        println!("Initialized Sterna");
+        return Ok(());
+    };
 
-    println!("Initialized Sterna");
+    let commit = init_from(&repo, &remote_name)?;
+    println!(
+        "Initialized Sterna from {remote_name} ({}): {} issues, {} edges",
+        &commit.id().to_string()[..7],
+        snapshot::load_issues(&repo)?.len(),
+        snapshot::load_edges(&repo)?.len()
+    );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::testing::{clones, head, open_clone};
+
+    #[test]
+    fn test_init_from_adopts_remote_history() {
+        let (dir, a, b, id) = clones();
+        assert_eq!(head(&b).id(), head(&a).id());
+        assert_eq!(snapshot::load_issue(&b, &id).unwrap().title, "Original");
+        assert_eq!(
+            snapshot::remote_commit(&b, "origin").unwrap().id(),
+            head(&a).id()
+        );
+
+        assert!(matches!(
+            init_from(&b, "origin"),
+            Err(Error::AlreadyInitialized)
+        ));
+        // A path works as well as a remote name
+        let c = open_clone(dir.path(), "c", "c@x");
+        let origin = dir.path().join("origin.git");
+        init_from(&c, origin.to_str().unwrap()).unwrap();
+        assert_eq!(head(&c).id(), head(&a).id());
+    }
+
+    #[test]
+    fn test_init_from_remote_without_snapshot() {
+        let dir = TempDir::new().unwrap();
+        Repository::init_bare(dir.path().join("origin.git")).unwrap();
+        let repo = open_clone(dir.path(), "a", "a@x");
+
+        assert!(matches!(
+            init_from(&repo, "origin"),
+            Err(Error::RemoteNotInitialized(_))
+        ));
+        assert!(!snapshot::is_initialized(&repo));
+    }
+}
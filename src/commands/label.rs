@@ -0,0 +1,72 @@
+use git2::Repository;
+
+use crate::error::Error;
+use crate::snapshot;
+use crate::storage;
+use crate::types::Issue;
+
+/// Add a label to an issue. Returns false if it already had the label.
+pub fn add_label(repo: &Repository, id_prefix: &str, label: &str) -> Result<bool, Error> {
+    edit_labels(repo, id_prefix, "Add label", label, |labels| {
+        if labels.iter().any(|l| l == label) {
+            return false;
+        }
+        labels.push(label.to_string());
+        true
+    })
+}
+
+/// Remove a label from an issue. Returns false if it did not have the label.
+pub fn remove_label(repo: &Repository, id_prefix: &str, label: &str) -> Result<bool, Error> {
+    edit_labels(repo, id_prefix, "Remove label", label, |labels| {
+        let len = labels.len();
+        labels.retain(|l| l != label);
+        labels.len() != len
+    })
+}
+
+/// Apply a single add or remove; only the delta is recorded in the label tags,
+/// so concurrent label edits on other clones are kept on merge
+fn edit_labels(
+    repo: &Repository,
+    id_prefix: &str,
+    action: &str,
+    label: &str,
+    change: impl FnOnce(&mut Vec<String>) -> bool,
+) -> Result<bool, Error> {
+    let id = snapshot::find_issue_id(repo, id_prefix)?;
+    let before: Issue = snapshot::load_issue(repo, &id)?;
+    let mut issue = before.clone();
+
+    if !change(&mut issue.labels) {
+        return Ok(false);
+    }
+    issue.record_edit(&before, storage::get_editor(repo)?, snapshot::clock(repo)?);
+
+    snapshot::save_issue(repo, &issue, &format!("{action} {label} on {id}"))?;
+    Ok(true)
+}
+
+pub fn add(id_prefix: String, label: String) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let id = snapshot::find_issue_id(&repo, &id_prefix)?;
+
+    if add_label(&repo, &id, &label)? {
+        println!("Added label {label} to {id}");
+    } else {
+        println!("{id} already has label {label}");
+    }
+    Ok(())
+}
+
+pub fn remove(id_prefix: String, label: String) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let id = snapshot::find_issue_id(&repo, &id_prefix)?;
+
+    if remove_label(&repo, &id, &label)? {
+        println!("Removed label {label} from {id}");
+    } else {
+        println!("Label not found: {label} on {id}");
+    }
+    Ok(())
+}
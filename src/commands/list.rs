@@ -1,35 +1,58 @@
-use git2::Repository;
+use git2::{Commit, Repository};
 
 use crate::error::Error;
 use crate::snapshot;
 use crate::types::{Issue, IssueType, Status};
 
-pub fn run(status: Option<String>, issue_type: Option<String>, json: bool) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
-
-    let status_filter = status.map(|s| parse_status(&s)).transpose()?;
-    let type_filter = issue_type.map(|t| IssueType::from_str(&t)).transpose()?;
-
-    let all_issues = snapshot::load_issues(&repo)?;
+/// List issues matching the optional filters, sorted by priority then age.
+/// Reads the snapshot at `at` when given, otherwise the current one.
+pub fn list(
+    repo: &Repository,
+    at: Option<&Commit>,
+    status: Option<Status>,
+    issue_type: Option<IssueType>,
+) -> Result<Vec<Issue>, Error> {
+    let all_issues = match at {
+        Some(commit) => snapshot::load_issues_at(repo, commit)?,
+        None => snapshot::load_issues(repo)?,
+    };
 
     let mut issues: Vec<Issue> = all_issues
         .into_values()
         .filter(|issue| {
-            if let Some(ref s) = status_filter {
-                if issue.status != *s {
-                    return false;
-                }
+            if let Some(s) = status
+                && issue.status != s
+            {
+                return false;
             }
-            if let Some(ref t) = type_filter {
-                if issue.issue_type != *t {
-                    return false;
-                }
+            if let Some(t) = issue_type
+                && issue.issue_type != t
+            {
+                return false;
             }
             true
         })
         .collect();
 
-    issues.sort_by(|a, b| (a.priority, a.created_at).cmp(&(b.priority, b.created_at)));
+    issues.sort_by_key(|i| (i.priority, i.created_at));
+    Ok(issues)
+}
+
+pub fn run(
+    at: Option<String>,
+    status: Option<String>,
+    issue_type: Option<String>,
+    json: bool,
+) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let at = at
+        .map(|spec| snapshot::resolve_at(&repo, &spec))
+        .transpose()?;
+
+    let status_filter = status.map(|s| s.parse()).transpose()?;
+    let type_filter = issue_type.map(|t| t.parse()).transpose()?;
+
+    let issues = list(&repo, at.as_ref(), status_filter, type_filter)?;
 
     if json {
         println!("{}", serde_json::to_string_pretty(&issues)?);
@@ -41,35 +64,19 @@ pub fn run(status: Option<String>, issue_type: Option<String>, json: bool) -> Re
         println!("{}", "-".repeat(60));
         for issue in issues {
             println!(
-                "{:<12} {:<12} {:<8} {:<10} {}",
+                "{:<12} {:<12} {:<8} {:<10} {}{}",
                 issue.id,
-                status_str(issue.status),
+                issue.status.as_str(),
                 issue.priority.as_str(),
                 issue.issue_type.as_str(),
-                truncate(&issue.title, 40)
+                truncate(&issue.title, 40),
+                if issue.conflict { " [conflict]" } else { "" }
             );
         }
     }
     Ok(())
 }
 
-fn parse_status(s: &str) -> Result<Status, Error> {
-    match s.to_lowercase().as_str() {
-        "open" => Ok(Status::Open),
-        "in_progress" | "inprogress" | "in-progress" => Ok(Status::InProgress),
-        "closed" => Ok(Status::Closed),
-        _ => Err(Error::InvalidPriority(format!("Unknown status: {s}"))),
-    }
-}
-
-fn status_str(s: Status) -> &'static str {
-    match s {
-        Status::Open => "open",
-        Status::InProgress => "in_progress",
-        Status::Closed => "closed",
-    }
-}
-
 fn truncate(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
         s.to_string()
@@ -0,0 +1,397 @@
+use std::collections::{BTreeSet, HashMap};
+
+use git2::{Commit, Oid, Repository, Tree};
+use serde::Serialize;
+
+use crate::commands::get::format_timestamp;
+use crate::date;
+use crate::error::Error;
+use crate::snapshot;
+use crate::types::{Edge, EdgeType, Issue, Status};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Init,
+    Created,
+    Updated,
+    Claimed,
+    Released,
+    Closed,
+    Reopened,
+    Deleted,
+    EdgeAdded,
+    EdgeRemoved,
+    Pull,
+    Import,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Init => "init",
+            EventKind::Created => "created",
+            EventKind::Updated => "updated",
+            EventKind::Claimed => "claimed",
+            EventKind::Released => "released",
+            EventKind::Closed => "closed",
+            EventKind::Reopened => "reopened",
+            EventKind::Deleted => "deleted",
+            EventKind::EdgeAdded => "edge_added",
+            EventKind::EdgeRemoved => "edge_removed",
+            EventKind::Pull => "pull",
+            EventKind::Import => "import",
+        }
+    }
+}
+
+/// A semantic event decoded from one snapshot commit
+#[derive(Serialize, Debug, Clone)]
+pub struct LogEvent {
+    pub commit: String,
+    pub timestamp: i64,
+    pub editor: String,
+    pub event: EventKind,
+    /// Issues the event is about (edge events list source then target)
+    pub issues: Vec<String>,
+    pub edge_type: Option<EdgeType>,
+    pub message: String,
+}
+
+/// Filters for the activity feed; `None` matches everything
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub since: Option<i64>,
+    pub editor: Option<String>,
+    pub issue: Option<String>,
+}
+
+/// Decode every snapshot commit into events, oldest first
+pub fn log(repo: &Repository, filter: &LogFilter) -> Result<Vec<LogEvent>, Error> {
+    let issue_filter = match filter.issue {
+        // Deleted issues too: the log is where their deletion shows up
+        Some(ref prefix) => Some(snapshot::find_any_issue_id(repo, prefix)?),
+        None => None,
+    };
+
+    let mut events = Vec::new();
+    for commit in snapshot::all_commits(repo)? {
+        if let Some(since) = filter.since
+            && commit.time().seconds() < since
+        {
+            continue;
+        }
+
+        for event in decode_commit(repo, &commit)? {
+            if let Some(ref editor) = filter.editor
+                && event.editor != *editor
+            {
+                continue;
+            }
+            if let Some(ref id) = issue_filter
+                && !event.issues.contains(id)
+            {
+                continue;
+            }
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+/// Turn a snapshot commit into events by diffing it against its first parent
+fn decode_commit(repo: &Repository, commit: &Commit) -> Result<Vec<LogEvent>, Error> {
+    let message = commit.summary().unwrap_or("").to_string();
+    let author = commit.author().email().unwrap_or("").to_string();
+    let event = |kind: EventKind, editor: &str, issues: Vec<String>, edge_type| LogEvent {
+        commit: commit.id().to_string(),
+        timestamp: commit.time().seconds(),
+        editor: editor.to_string(),
+        event: kind,
+        issues,
+        edge_type,
+        message: message.clone(),
+    };
+
+    let Ok(parent) = commit.parent(0) else {
+        return Ok(vec![event(EventKind::Init, &author, Vec::new(), None)]);
+    };
+
+    let new_tree = commit.tree()?;
+    let old_tree = parent.tree()?;
+    let issue_changes = changed_issues(repo, &old_tree, &new_tree)?;
+    let (edges_added, edges_removed) = changed_edges(repo, &old_tree, &new_tree)?;
+
+    // Merges and batch imports are reported as a single event
+    let batch_kind = if commit.parent_count() > 1 || message.starts_with("Pull from") {
+        Some(EventKind::Pull)
+    } else if message.starts_with("Import") {
+        Some(EventKind::Import)
+    } else {
+        None
+    };
+    if let Some(kind) = batch_kind {
+        let mut touched = BTreeSet::new();
+        for (_, new) in &issue_changes {
+            touched.insert(new.id.clone());
+        }
+        for edge in edges_added.iter().chain(&edges_removed) {
+            touched.insert(edge.source.clone());
+            touched.insert(edge.target.clone());
+        }
+        return Ok(vec![event(
+            kind,
+            &author,
+            touched.into_iter().collect(),
+            None,
+        )]);
+    }
+
+    let mut events = Vec::new();
+    for (old, new) in &issue_changes {
+        events.push(event(
+            issue_event_kind(old.as_ref(), new),
+            &new.editor,
+            vec![new.id.clone()],
+            None,
+        ));
+    }
+    for (kind, edges) in [
+        (EventKind::EdgeAdded, &edges_added),
+        (EventKind::EdgeRemoved, &edges_removed),
+    ] {
+        for edge in edges {
+            events.push(event(
+                kind,
+                &author,
+                vec![edge.source.clone(), edge.target.clone()],
+                Some(edge.edge_type),
+            ));
+        }
+    }
+    Ok(events)
+}
+
+fn issue_event_kind(old: Option<&Issue>, new: &Issue) -> EventKind {
+    let Some(old) = old else {
+        return EventKind::Created;
+    };
+    if !old.deleted && new.deleted {
+        EventKind::Deleted
+    } else if old.status != Status::Closed && new.status == Status::Closed {
+        EventKind::Closed
+    } else if old.status == Status::Closed && new.status != Status::Closed {
+        EventKind::Reopened
+    } else if !old.claimed && new.claimed {
+        EventKind::Claimed
+    } else if old.claimed && !new.claimed {
+        EventKind::Released
+    } else {
+        EventKind::Updated
+    }
+}
+
+/// Issues whose blob differs between two snapshot trees, as (old, new) pairs
+fn changed_issues(
+    repo: &Repository,
+    old_tree: &Tree,
+    new_tree: &Tree,
+) -> Result<Vec<(Option<Issue>, Issue)>, Error> {
+    let old_entries = subtree_entries(repo, old_tree, "issues")?;
+    let new_entries = subtree_entries(repo, new_tree, "issues")?;
+
+    let mut changes = Vec::new();
+    let mut names: Vec<_> = new_entries.keys().collect();
+    names.sort();
+    for name in names {
+        let new_oid = new_entries[name];
+        let old_oid = old_entries.get(name).copied();
+        if old_oid == Some(new_oid) {
+            continue;
+        }
+        let new = snapshot::read_issue_blob(repo, new_oid)?;
+        let old = old_oid
+            .map(|oid| snapshot::read_issue_blob(repo, oid))
+            .transpose()?;
+        changes.push((old, new));
+    }
+    Ok(changes)
+}
+
+/// Edges live only in the new tree, and edges live only in the old tree.
+/// A tombstone counts as absent.
+fn changed_edges(
+    repo: &Repository,
+    old_tree: &Tree,
+    new_tree: &Tree,
+) -> Result<(Vec<Edge>, Vec<Edge>), Error> {
+    let old_entries = subtree_entries(repo, old_tree, "edges")?;
+    let new_entries = subtree_entries(repo, new_tree, "edges")?;
+
+    let read = |oid: Oid| -> Result<Edge, Error> {
+        let blob = repo.find_blob(oid)?;
+        Edge::from_json(blob.content())
+    };
+    let live = |entries: &HashMap<String, Oid>, name: &str| -> Result<Option<Edge>, Error> {
+        match entries.get(name) {
+            Some(oid) => Ok(Some(read(*oid)?).filter(Edge::is_live)),
+            None => Ok(None),
+        }
+    };
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut names: BTreeSet<&String> = old_entries.keys().collect();
+    names.extend(new_entries.keys());
+    for name in names {
+        if old_entries.get(name) == new_entries.get(name) {
+            continue;
+        }
+        match (live(&old_entries, name)?, live(&new_entries, name)?) {
+            (None, Some(edge)) => added.push(edge),
+            (Some(edge), None) => removed.push(edge),
+            _ => {}
+        }
+    }
+    Ok((added, removed))
+}
+
+fn subtree_entries(
+    repo: &Repository,
+    tree: &Tree,
+    name: &str,
+) -> Result<HashMap<String, Oid>, Error> {
+    let subtree = snapshot::get_subtree(repo, tree, name)?;
+    Ok(subtree
+        .iter()
+        .filter_map(|entry| entry.name().map(|n| (n.to_string(), entry.id())))
+        .collect())
+}
+
+pub fn run(
+    since: Option<String>,
+    editor: Option<String>,
+    issue: Option<String>,
+    json: bool,
+    jsonl: bool,
+) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let filter = LogFilter {
+        since: since.map(|s| date::parse_date(&s)).transpose()?,
+        editor,
+        issue,
+    };
+
+    let events = log(&repo, &filter)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&events)?);
+    } else if jsonl {
+        for event in events {
+            println!("{}", serde_json::to_string(&event)?);
+        }
+    } else {
+        for event in events {
+            let subject = match event.edge_type {
+                Some(edge_type) => format!(
+                    "{} {} {}",
+                    event.issues[0],
+                    edge_type.as_str(),
+                    event.issues[1]
+                ),
+                None if event.issues.len() == 1 => event.issues[0].clone(),
+                None => event.message.clone(),
+            };
+            println!(
+                "{}  {:<12} {:<20} {}",
+                format_timestamp(event.timestamp),
+                event.event.as_str(),
+                event.editor,
+                subject
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{claim, close, delete, dep};
+    use crate::testing::{create_issue, repo};
+
+    fn kinds(events: &[LogEvent]) -> Vec<EventKind> {
+        events.iter().map(|e| e.event).collect()
+    }
+
+    #[test]
+    fn test_log_decodes_commits_oldest_first() {
+        let (_dir, repo) = repo();
+        let a = create_issue(&repo, "A");
+        let b = create_issue(&repo, "B");
+        claim::claim(&repo, &a.id, None).unwrap();
+        dep::add_edge(&repo, &a.id, &b.id, EdgeType::DependsOn).unwrap();
+        close::close(&repo, &a.id, None).unwrap();
+        delete::delete(&repo, &b.id).unwrap();
+
+        let events = log(&repo, &LogFilter::default()).unwrap();
+        assert_eq!(
+            kinds(&events),
+            vec![
+                EventKind::Init,
+                EventKind::Created,
+                EventKind::Created,
+                EventKind::Claimed,
+                EventKind::EdgeAdded,
+                EventKind::Closed,
+                EventKind::Deleted,
+                EventKind::EdgeRemoved,
+            ]
+        );
+        assert_eq!(events[3].issues, vec![a.id.clone()]);
+        assert_eq!(events[4].issues, vec![a.id.clone(), b.id.clone()]);
+        assert_eq!(events[4].edge_type, Some(EdgeType::DependsOn));
+        assert!(events.iter().all(|e| e.editor == "a@x"));
+    }
+
+    #[test]
+    fn test_log_filters() {
+        let (_dir, repo) = repo();
+        let a = create_issue(&repo, "A");
+        repo.config().unwrap().set_str("user.email", "b@x").unwrap();
+        let b = create_issue(&repo, "B");
+        dep::add_edge(&repo, &a.id, &b.id, EdgeType::RelatesTo).unwrap();
+        delete::delete(&repo, &b.id).unwrap();
+
+        // An issue filter matches edge events on either end, and deleted issues
+        let filter = LogFilter {
+            issue: Some(b.id.clone()),
+            ..Default::default()
+        };
+        let events = log(&repo, &filter).unwrap();
+        assert_eq!(
+            kinds(&events),
+            vec![
+                EventKind::Created,
+                EventKind::EdgeAdded,
+                EventKind::Deleted,
+                EventKind::EdgeRemoved,
+            ]
+        );
+
+        let filter = LogFilter {
+            editor: Some("a@x".to_string()),
+            ..Default::default()
+        };
+        let events = log(&repo, &filter).unwrap();
+        assert_eq!(kinds(&events), vec![EventKind::Init, EventKind::Created]);
+        assert_eq!(events[1].issues, vec![a.id]);
+
+        let filter = LogFilter {
+            since: Some(chrono::Utc::now().timestamp() + 60),
+            ..Default::default()
+        };
+        assert!(log(&repo, &filter).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+use git2::Repository;
+
+use crate::error::Error;
+use crate::migration;
+use crate::snapshot::{self, Changeset};
+use crate::types::{Conflict, Edge, Issue, SCHEMA_VERSION};
+
+/// Number of payloads rewritten by a migration
+#[derive(Debug, Clone, Default)]
+pub struct MigrateSummary {
+    pub issues: usize,
+    pub edges: usize,
+    pub conflicts: usize,
+}
+
+/// Rewrite every issue, edge and conflict record stored with an older schema
+/// version at the current version, in a single commit
+pub fn migrate(repo: &Repository) -> Result<MigrateSummary, Error> {
+    if !snapshot::is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let commit = snapshot::get_snapshot_commit(repo)?;
+    let tree = commit.tree()?;
+    let mut changes = Changeset::default();
+
+    for entry in snapshot::get_subtree(repo, &tree, "issues")?.iter() {
+        let blob = repo.find_blob(entry.id())?;
+        let value: serde_json::Value = serde_json::from_slice(blob.content())?;
+        if migration::version_of(&value) < SCHEMA_VERSION {
+            changes.issues.push(Issue::from_value(value)?);
+        }
+    }
+    for entry in snapshot::get_subtree(repo, &tree, "edges")?.iter() {
+        let blob = repo.find_blob(entry.id())?;
+        let value: serde_json::Value = serde_json::from_slice(blob.content())?;
+        if migration::version_of(&value) < SCHEMA_VERSION {
+            changes.edges.push(Edge::from_value(value)?);
+        }
+    }
+    if let Some(conflicts_tree) = snapshot::get_optional_subtree(repo, &tree, "conflicts")? {
+        for entry in conflicts_tree.iter() {
+            let blob = repo.find_blob(entry.id())?;
+            let value: serde_json::Value = serde_json::from_slice(blob.content())?;
+            let outdated = [&value, &value["local"], &value["remote"]]
+                .into_iter()
+                .any(|v| migration::version_of(v) < SCHEMA_VERSION);
+            if outdated {
+                changes.conflicts.push(Conflict::from_value(value)?);
+            }
+        }
+    }
+
+    let summary = MigrateSummary {
+        issues: changes.issues.len(),
+        edges: changes.edges.len(),
+        conflicts: changes.conflicts.len(),
+    };
+    snapshot::commit_changes(
+        repo,
+        &changes,
+        &format!("Migrate snapshot to schema v{SCHEMA_VERSION}"),
+    )?;
+
+    Ok(summary)
+}
+
+pub fn run() -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let summary = migrate(&repo)?;
+
+    if summary.issues == 0 && summary.edges == 0 && summary.conflicts == 0 {
+        println!("Snapshot is already at schema v{SCHEMA_VERSION}");
+    } else {
+        println!(
+            "Migrated {} issues, {} edges, {} conflicts to schema v{SCHEMA_VERSION}",
+            summary.issues, summary.edges, summary.conflicts
+        );
+    }
+    Ok(())
+}
@@ -1,19 +1,32 @@
 pub mod claim;
 pub mod close;
+pub mod conflicts;
 pub mod create;
+pub mod delete;
 pub mod dep;
+pub mod diff;
 pub mod export;
+pub mod fetch;
+pub mod fsck;
 pub mod get;
+pub mod history;
 pub mod import;
 pub mod init;
+pub mod label;
 pub mod list;
+pub mod log;
+pub mod migrate;
 pub mod onboard;
 pub mod prime;
 pub mod pull;
 pub mod purge;
 pub mod push;
+pub mod quarantine;
 pub mod ready;
 pub mod release;
+pub mod remote;
 pub mod reopen;
+pub mod status;
 pub mod sync;
+pub mod undo;
 pub mod update;
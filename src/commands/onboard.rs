@@ -11,6 +11,7 @@ Git-native issue tracker for AI coding agents. Issues persist in Git refs, survi
 
 ```bash
 st init                    # Initialize in a Git repo
+st init --from origin      # ...or adopt the team's issues after a fresh clone
 st create "Fix bug" -d "Description" --type bug --priority 2
 st ready                   # See available work
 ```
@@ -22,6 +23,10 @@ st ready                   # See available work
 - `st get <id> [--json]` - Show issue details
 - `st list [--status S] [--type T] [--json]` - List issues
 - `st update <id> [--title T] [--description D] [--priority N]` - Update issue
+- `st label add <id> <label>` / `st label remove <id> <label>` - Add or remove one label
+- `st history <id> [--json]` - Show change timeline of an issue
+- `st log [--since D] [--editor E] [--issue ID] [--json|--jsonl]` - Activity feed
+- `st diff [from] [to] [--json]` - Compare commits, dates, fetched remotes or export files
 
 **Types:** epic, task, bug, feature, chore
 **Priority:** 0 (critical) to 4 (backlog)
@@ -33,6 +38,11 @@ st ready                   # See available work
 - `st release <id> [--reason "..."]` - Release claim
 - `st close <id> [--reason "..."]` - Close issue
 - `st reopen <id> [--reason "..."]` - Reopen issue
+- `st delete <id>` - Delete issue (spam, duplicates, tests)
+- `st pull [--strict] [--dry-run] [--json]` - Merge remote changes; lists claims you lost to other agents
+- `st fetch` then `st status [--json]` - See what is unpushed or unmerged, and your claims
+- `st remote setup` - Let plain `git fetch`/`git push` carry issues too
+- `st conflicts` - Edits a merge discarded; `st conflicts resolve <id> --take local|remote|merged`
 
 ### Dependencies
 - `st dep add <src> --needs <tgt>` - src depends on tgt
@@ -41,10 +51,12 @@ st ready                   # See available work
 - `st dep add <src> --parent <tgt>` - parent-child hierarchy
 - `st dep add <src> --duplicates <tgt>` - mark duplicate
 - `st dep remove <src> --needs <tgt>` - remove dependency
+- `st quarantine` - Edges a pull set aside; `st quarantine resolve <edge> --restore|--drop`
 
 ### Data
 - `st export [--output file]` - Export to JSON
-- `st import <file>` - Import from JSON
+- `st import <file> [--dry-run]` - Import from JSON; `--dry-run` shows what would change
+- `st undo [-n N] [--dry-run]` - Revert the last N operations
 - `st purge` - Remove all Sterna data
 
 ## Session Context
@@ -55,7 +55,7 @@ pub fn run(export: bool) -> Result<(), Error> {
 
     // Always show ready issues
     eprintln!("## Ready Issues");
-    ready::run(false)?;
+    ready::run(None, false)?;
 
     Ok(())
 }
@@ -1,96 +1,694 @@
-use git2::Repository;
+use std::collections::{HashMap, HashSet};
 
+use git2::{Commit, ErrorCode, Repository};
+use serde::Serialize;
+
+use crate::commands::diff::{self, DiffReport, TrackerState};
+use crate::commands::{fetch, init};
+use crate::dag;
 use crate::error::Error;
-use crate::snapshot;
-use crate::types::{Edge, Issue};
+use crate::merge;
+use crate::snapshot::{self, Changeset};
+use crate::storage;
+use crate::types::{Conflict, Edge, Issue, Status};
 
-pub fn run(remote: Option<String>) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
-    let remote_name = remote.unwrap_or_else(|| "origin".to_string());
+/// Outcome of merging a remote snapshot into the local one
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PullSummary {
+    pub issues_added: usize,
+    pub issues_updated: usize,
+    pub issues_deleted: usize,
+    pub edges_added: usize,
+    pub edges_removed: usize,
+    /// Edges set aside because the merged graph had a cycle or lost an issue
+    pub edges_quarantined: Vec<Edge>,
+    /// Claims by the current editor that a concurrent remote edit overwrote
+    pub lost_claims: Vec<LostClaim>,
+    /// Concurrent versions this merge discarded, recorded under `conflicts/`
+    pub conflicts: Vec<Conflict>,
+    /// Why fetching failed, when the previously fetched tracking ref was
+    /// merged instead
+    pub fetch_error: Option<String>,
+    /// Live issues and edges before and after the merge, field by field
+    pub changes: DiffReport,
+}
+
+/// A claim held locally before the pull that the merged state no longer has
+#[derive(Serialize, Debug, Clone)]
+pub struct LostClaim {
+    pub id: String,
+    pub title: String,
+    /// New claimant, or else editor of the remote version that won
+    pub by: String,
+    pub status: Status,
+    pub claimed: bool,
+    pub deleted: bool,
+}
+
+impl LostClaim {
+    /// What happened to the claim, e.g. "claimed by b@x"
+    fn describe(&self) -> String {
+        let what = if self.deleted {
+            "deleted".to_string()
+        } else if self.claimed {
+            "claimed".to_string()
+        } else {
+            format!("set to {}", self.status.as_str())
+        };
+        format!("{what} by {}", self.by)
+    }
+}
+
+/// Fetch the snapshot from a remote into its tracking ref and merge it into
+/// the local snapshot. Diverged histories are joined by a merge commit with
+/// the local and remote heads as parents, so the next push is a fast-forward.
+/// An uninitialized repository adopts the remote snapshot. If the remote
+/// cannot be reached, a tracking ref fetched earlier (by `st fetch`, or by
+/// plain `git fetch` after `st remote setup`) is merged instead.
+///
+/// With `dry_run` the remote is still fetched, but the local snapshot is left
+/// alone and the summary describes what the merge would write.
+pub fn pull(repo: &Repository, remote_name: &str, dry_run: bool) -> Result<PullSummary, Error> {
+    // Nothing local to merge: take the remote snapshot and its history as-is
+    if !snapshot::is_initialized(repo) {
+        let commit = if dry_run {
+            fetch::fetch(repo, remote_name)?
+        } else {
+            init::init_from(repo, remote_name)?
+        };
+        let remote = TrackerState {
+            label: remote_label(remote_name, &commit),
+            issues: snapshot::load_issues_at(repo, &commit)?,
+            edges: snapshot::load_edges_at(repo, &commit)?,
+        };
+        return Ok(PullSummary {
+            issues_added: remote.issues.len(),
+            edges_added: remote.edges.len(),
+            changes: diff::diff(&TrackerState::empty("no snapshot"), &remote),
+            ..Default::default()
+        });
+    }
+
+    let (remote_commit, fetch_error) = match fetch::fetch(repo, remote_name) {
+        Ok(commit) => (commit, None),
+        Err(Error::Git(e)) => match snapshot::remote_commit(repo, remote_name) {
+            Some(commit) => (commit, Some(e.message().to_string())),
+            None => return Err(e.into()),
+        },
+        Err(e) => return Err(e),
+    };
+    Ok(PullSummary {
+        fetch_error,
+        ..merge_remote(repo, remote_name, &remote_commit, dry_run)?
+    })
+}
+
+/// Label of the merge result in a summary's `changes`
+fn remote_label(remote_name: &str, commit: &Commit) -> String {
+    format!(
+        "after pull from {remote_name} ({})",
+        &commit.id().to_string()[..7]
+    )
+}
+
+/// Merge a fetched remote snapshot commit into the local snapshot
+fn merge_remote(
+    repo: &Repository,
+    remote_name: &str,
+    remote_commit: &Commit,
+    dry_run: bool,
+) -> Result<PullSummary, Error> {
+    let local_commit = snapshot::get_snapshot_commit(repo)?;
+    let (local_id, remote_id) = (local_commit.id(), remote_commit.id());
 
-    let mut git_remote = repo.find_remote(&remote_name)?;
-    git_remote.fetch(&["refs/sterna/snapshot:refs/sterna/remote"], None, None)?;
+    // Everything the remote has is already part of local history
+    if local_id == remote_id || repo.graph_descendant_of(local_id, remote_id)? {
+        return Ok(PullSummary::default());
+    }
 
-    let remote_ref = repo.find_reference("refs/sterna/remote")?;
-    let remote_commit = remote_ref.peel_to_commit()?;
     let remote_tree = remote_commit.tree()?;
+    let remote_meta = snapshot::load_meta_at(repo, remote_commit)?;
+    let mut changes = Changeset {
+        witness: remote_meta.lamport,
+        merge_parent: Some(remote_id),
+        ..Default::default()
+    };
+
+    // Identical manifests mean there is nothing to merge, only history to join
+    if remote_meta.same_content(&snapshot::load_meta(repo)?) {
+        if dry_run {
+            return Ok(PullSummary::default());
+        }
+        snapshot::commit_changes(
+            repo,
+            &changes,
+            &format!("Pull from {remote_name}: 0 issues, 0 edges"),
+        )?;
+        return Ok(PullSummary::default());
+    }
 
-    let local_issues = snapshot::load_issues(&repo)?;
-    let local_edges = snapshot::load_edges(&repo)?;
+    let local_issues = snapshot::load_all_issues(repo)?;
+    let editor = storage::get_editor(repo)?;
+    let mut lost_claims = Vec::new();
+    // The last common version of each issue, for three-way description merges
+    let base_issues = match repo.merge_base(local_id, remote_id) {
+        Ok(base) => snapshot::load_all_issues_at(repo, &repo.find_commit(base)?)?,
+        Err(e) if e.code() == ErrorCode::NotFound => Default::default(),
+        Err(e) => return Err(e.into()),
+    };
+    let local_edges = snapshot::load_all_edges(repo)?;
 
     let mut issues_to_save: Vec<Issue> = Vec::new();
-    let mut edges_to_add: Vec<Edge> = Vec::new();
+    let mut edges_to_save: Vec<Edge> = Vec::new();
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    let source = format!("pull from {remote_name}");
+    let conflict_clock = snapshot::clock(repo)?.max(remote_meta.lamport) + 1;
+
+    // Payloads from a newer sterna cannot be merged; say where they came from
+    let remote_schema = |e: Error| match e {
+        Error::SchemaTooNew { supported, found } => Error::RemoteSchemaTooNew {
+            remote: remote_name.to_string(),
+            supported,
+            found,
+        },
+        e => e,
+    };
 
     let issues_tree_entry = remote_tree
         .get_name("issues")
         .ok_or(Error::InvalidSnapshot)?;
-    let issues_tree = issues_tree_entry.to_object(&repo)?.peel_to_tree()?;
+    let issues_tree = issues_tree_entry.to_object(repo)?.peel_to_tree()?;
 
     for entry in issues_tree.iter() {
         let blob = repo.find_blob(entry.id())?;
-        let remote_issue = Issue::from_json(blob.content())?;
+        let remote_issue = Issue::from_json(blob.content()).map_err(remote_schema)?;
 
-        let dominated = if let Some(existing) = local_issues.get(&remote_issue.id) {
-            remote_issue.lamport > existing.lamport
-                || (remote_issue.lamport == existing.lamport
-                    && remote_issue.updated_at > existing.updated_at)
-        } else {
-            true
+        let merged = match local_issues.get(&remote_issue.id) {
+            Some(existing) => {
+                let base = base_issues.get(&remote_issue.id);
+                let merged = merge::merge_issue_with_base(existing, &remote_issue, base);
+                lost_claims.extend(lost_claim(existing, &merged, &remote_issue, &editor));
+                let fields = merge::discarded_fields(existing, &remote_issue, &merged, base);
+                if !fields.is_empty() {
+                    conflicts.push(Conflict::record(
+                        existing,
+                        &remote_issue,
+                        fields,
+                        &source,
+                        &editor,
+                        conflict_clock,
+                    ));
+                }
+                merged
+            }
+            None => remote_issue,
         };
 
-        if dominated {
-            issues_to_save.push(remote_issue);
+        if local_issues.get(&merged.id) != Some(&merged) {
+            issues_to_save.push(merged);
         }
     }
 
-    // Merge edges from remote (union)
+    // Conflict records merge per entry; the ones this pull records are at a
+    // clock above both sides, so they are new entries
+    let local_conflicts = snapshot::load_conflicts(repo)?;
+    let mut conflicts_to_save: Vec<Conflict> = Vec::new();
+    for remote_conflict in
+        snapshot::load_conflicts_at(repo, remote_commit).map_err(remote_schema)?
+    {
+        let name = snapshot::conflict_name(&remote_conflict);
+        let local = local_conflicts
+            .iter()
+            .find(|c| snapshot::conflict_name(c) == name);
+        let merged = match local {
+            Some(existing) => merge::merge_conflict(existing, &remote_conflict),
+            None => remote_conflict,
+        };
+        if local != Some(&merged) {
+            conflicts_to_save.push(merged);
+        }
+    }
+    conflicts_to_save.extend(conflicts.iter().cloned());
+
+    // Merge edges from remote (tombstones included)
     let edges_tree_entry = remote_tree
         .get_name("edges")
         .ok_or(Error::InvalidSnapshot)?;
-    let edges_tree = edges_tree_entry.to_object(&repo)?.peel_to_tree()?;
+    let edges_tree = edges_tree_entry.to_object(repo)?.peel_to_tree()?;
 
     for entry in edges_tree.iter() {
         let blob = repo.find_blob(entry.id())?;
-        let remote_edge = Edge::from_json(blob.content())?;
+        let remote_edge = Edge::from_json(blob.content()).map_err(remote_schema)?;
 
-        let exists = local_edges.iter().any(|e| {
-            e.source == remote_edge.source
-                && e.target == remote_edge.target
-                && e.edge_type == remote_edge.edge_type
-        });
+        let local = local_edges.iter().find(|e| e.same_key(&remote_edge));
+        let remote_edge = match local {
+            Some(existing) => merge::merge_edge(existing, &remote_edge),
+            None => remote_edge,
+        };
+        if local == Some(&remote_edge) {
+            continue;
+        }
+        edges_to_save.push(remote_edge);
+    }
+
+    // Each side's graph was valid, but their union may have a cycle or an
+    // edge to an issue the other side deleted
+    let edges_quarantined = quarantine_edges(
+        &local_issues,
+        &issues_to_save,
+        &local_edges,
+        &mut edges_to_save,
+        &editor,
+        snapshot::clock(repo)?.max(remote_meta.lamport),
+    );
 
-        if !exists {
-            edges_to_add.push(remote_edge);
+    let mut edges_added = 0;
+    let mut edges_removed = 0;
+    for edge in &edges_to_save {
+        let local = local_edges.iter().find(|e| e.same_key(edge));
+        match (local.is_some_and(Edge::is_live), edge.is_live()) {
+            (false, true) => edges_added += 1,
+            (true, false) => edges_removed += 1,
+            _ => {}
         }
     }
 
     // Single batch commit
     let issues_added = issues_to_save
         .iter()
-        .filter(|i| !local_issues.contains_key(&i.id))
+        .filter(|i| !i.deleted && !local_issues.contains_key(&i.id))
         .count();
-    let issues_updated = issues_to_save.len() - issues_added;
-    let edges_added = edges_to_add.len();
-
-    if !issues_to_save.is_empty() || !edges_to_add.is_empty() {
-        snapshot::merge_snapshot(
-            &repo,
-            &issues_to_save,
-            &edges_to_add,
-            &format!(
-                "Pull from {}: {} issues, {} edges",
-                remote_name,
-                issues_to_save.len(),
-                edges_to_add.len()
-            ),
-        )?;
+    // A tombstone for an issue never seen here is a deletion, not an addition
+    let issues_deleted = issues_to_save
+        .iter()
+        .filter(|i| i.deleted && local_issues.get(&i.id).is_none_or(|l| !l.deleted))
+        .count();
+    let issues_updated = issues_to_save.len() - issues_added - issues_deleted;
+    let message = format!(
+        "Pull from {}: {} issues, {} edges",
+        remote_name,
+        issues_to_save.len(),
+        edges_to_save.len()
+    );
+    let before = diff::resolve(repo, None, false)?;
+    let after = before.with_changes(
+        remote_label(remote_name, remote_commit),
+        &issues_to_save,
+        &edges_to_save,
+    );
+    let report = diff::diff(&before, &after);
+    changes.issues = issues_to_save;
+    changes.edges = edges_to_save;
+    changes.conflicts = conflicts_to_save;
+
+    // When local history is behind, the remote head already is the merge
+    // result, unless it still needed edges quarantined or conflicts recorded
+    if !dry_run {
+        if edges_quarantined.is_empty()
+            && conflicts.is_empty()
+            && repo.graph_descendant_of(remote_id, local_id)?
+        {
+            if !snapshot::fast_forward(repo, local_id, remote_commit)? {
+                // A local write landed after local_commit was read; merge
+                // again on top of it rather than discard it
+                return merge_remote(repo, remote_name, remote_commit, dry_run);
+            }
+        } else {
+            snapshot::commit_changes(repo, &changes, &message)?;
+        }
     }
 
-    repo.find_reference("refs/sterna/remote")?.delete()?;
+    Ok(PullSummary {
+        issues_added,
+        issues_updated,
+        issues_deleted,
+        edges_added,
+        edges_removed,
+        edges_quarantined,
+        lost_claims,
+        conflicts,
+        fetch_error: None,
+        changes: report,
+    })
+}
+
+/// Validate the merged graph like `st dep add` does and quarantine the edges
+/// `dag::invalid_edges` picks, replacing their entries in `edges_to_save`.
+/// Returns the quarantined edges.
+pub(crate) fn quarantine_edges(
+    local_issues: &HashMap<String, Issue>,
+    issues_to_save: &[Issue],
+    local_edges: &[Edge],
+    edges_to_save: &mut Vec<Edge>,
+    editor: &str,
+    clock: u64,
+) -> Vec<Edge> {
+    let mut issues: HashMap<&str, &Issue> = local_issues
+        .iter()
+        .map(|(id, i)| (id.as_str(), i))
+        .collect();
+    issues.extend(issues_to_save.iter().map(|i| (i.id.as_str(), i)));
+    let issue_ids: HashSet<String> = issues
+        .values()
+        .filter(|i| !i.deleted)
+        .map(|i| i.id.clone())
+        .collect();
+
+    let mut edges: Vec<Edge> = local_edges
+        .iter()
+        .filter(|local| !edges_to_save.iter().any(|e| e.same_key(local)))
+        .cloned()
+        .collect();
+    edges.extend(edges_to_save.iter().cloned());
+
+    let mut quarantined = Vec::new();
+    for (index, reason) in dag::invalid_edges(&issue_ids, &edges) {
+        let mut edge = edges[index].clone();
+        edge.quarantine = Some(reason);
+        edge.record_edit(editor.to_string(), clock);
+        edges_to_save.retain(|e| !e.same_key(&edge));
+        edges_to_save.push(edge.clone());
+        quarantined.push(edge);
+    }
+    quarantined
+}
+
+/// Whether `editor`'s local claim on an issue did not survive the merge
+fn lost_claim(local: &Issue, merged: &Issue, remote: &Issue, editor: &str) -> Option<LostClaim> {
+    if !local.claimed || local.deleted || local.claimed_by.as_deref() != Some(editor) {
+        return None;
+    }
+    let claim = |i: &Issue| {
+        (
+            i.clocks.status,
+            i.status,
+            i.claimed,
+            i.claimed_by.clone(),
+            i.claimed_at,
+        )
+    };
+    if claim(merged) == claim(local) && !merged.deleted {
+        return None;
+    }
+    let by = match merged.claimed_by {
+        Some(ref claimant) if merged.claimed => claimant.clone(),
+        _ => remote.editor.clone(),
+    };
+    Some(LostClaim {
+        id: merged.id.clone(),
+        title: merged.title.clone(),
+        by,
+        status: merged.status,
+        claimed: merged.claimed,
+        deleted: merged.deleted,
+    })
+}
+
+pub fn run(remote: Option<String>, strict: bool, dry_run: bool, json: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let remote_name = remote.unwrap_or_else(|| "origin".to_string());
+
+    let summary = pull(&repo, &remote_name, dry_run)?;
 
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        print_summary(&remote_name, &summary, dry_run);
+    }
+
+    if strict && !summary.lost_claims.is_empty() {
+        return Err(Error::LostClaims(summary.lost_claims.len()));
+    }
+    Ok(())
+}
+
+pub(crate) fn print_summary(remote_name: &str, summary: &PullSummary, dry_run: bool) {
+    if let Some(reason) = &summary.fetch_error {
+        eprintln!(
+            "warning: could not fetch {remote_name} ({reason}); using the snapshot fetched earlier"
+        );
+    }
+    if dry_run {
+        diff::print_changes(&summary.changes);
+    }
+    let (pulled, quarantined, lose) = if dry_run {
+        ("Would pull", "Would quarantine", "Would lose")
+    } else {
+        ("Pulled", "Quarantined", "Lost")
+    };
     eprintln!(
-        "Pulled from {remote_name}: {issues_added} issues added, {issues_updated} issues updated, {edges_added} edges added"
+        "{pulled} from {remote_name}: {} issues added, {} issues updated, {} issues deleted, {} edges added, {} edges removed",
+        summary.issues_added,
+        summary.issues_updated,
+        summary.issues_deleted,
+        summary.edges_added,
+        summary.edges_removed
     );
+    for edge in &summary.edges_quarantined {
+        println!(
+            "{quarantined} edge {} {} {}: {}",
+            edge.source,
+            edge.edge_type.as_str(),
+            edge.target,
+            edge.quarantine.as_deref().unwrap_or("")
+        );
+    }
+    if !dry_run && !summary.edges_quarantined.is_empty() {
+        println!("Run 'st quarantine' to review them");
+    }
+    let recorded = if dry_run { "Would record" } else { "Recorded" };
+    for conflict in &summary.conflicts {
+        println!(
+            "{recorded} conflict on {} ({}): {}",
+            conflict.id,
+            conflict.title,
+            conflict.fields.join(", ")
+        );
+    }
+    if !dry_run && !summary.conflicts.is_empty() {
+        println!("Run 'st conflicts' to compare both versions");
+    }
+    for lost in &summary.lost_claims {
+        println!(
+            "{lose} claim on {} ({}): {}",
+            lost.id,
+            lost.title,
+            lost.describe()
+        );
+    }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{push, update};
+    use crate::testing::{clones, edit, head, make_edge, make_issue, refs};
+    use crate::types::{EdgeType, IssueType, Priority};
+
+    fn claim(issue: &mut Issue, editor: &str) {
+        issue.status = Status::InProgress;
+        issue.claimed = true;
+        issue.claimed_by = Some(editor.to_string());
+        issue.claimed_at = Some(200);
+    }
+
+    fn release(issue: &mut Issue) {
+        issue.status = Status::Open;
+        issue.claimed = false;
+        issue.claimed_by = None;
+        issue.claimed_at = None;
+    }
+
+    fn lost(local: &Issue, remote: &Issue) -> Option<LostClaim> {
+        lost_claim(local, &merge::merge_issue(local, remote), remote, "a@x")
+    }
+
+    #[test]
+    fn test_claim_survives_remote_edit_to_other_fields() {
+        let base = edit(&make_issue("st-a"), "a@x", |i| claim(i, "a@x"));
+        let local = edit(&base, "a@x", |i| i.priority = Priority::High);
+        // Someone else edited last, but the claim is still ours
+        let remote = edit(&base, "b@x", |i| i.title = "Renamed".to_string());
+
+        assert!(lost(&local, &remote).is_none());
+        assert!(lost(&remote, &local).is_none());
+    }
+
+    #[test]
+    fn test_remote_release_loses_claim() {
+        let base = make_issue("st-a");
+        let local = edit(&base, "a@x", |i| claim(i, "a@x"));
+        let remote = edit(&local, "b@x", release);
+
+        let lost = lost(&local, &remote).unwrap();
+        assert!(!lost.claimed);
+        assert_eq!(lost.status, Status::Open);
+        assert_eq!(lost.by, "b@x");
+    }
+
+    #[test]
+    fn test_later_remote_claim_loses_claim_to_claimant() {
+        let base = make_issue("st-a");
+        let local = edit(&base, "a@x", |i| claim(i, "a@x"));
+        let remote = edit(&base, "b@x", |i| claim(i, "b@x"));
+        let remote = edit(&remote, "c@x", |i| {
+            i.claim_context = Some("fix".to_string())
+        });
+
+        let lost = lost(&local, &remote).unwrap();
+        assert!(lost.claimed);
+        assert_eq!(lost.by, "b@x");
+    }
+
+    #[test]
+    fn test_remote_delete_loses_claim() {
+        let base = make_issue("st-a");
+        let local = edit(&base, "a@x", |i| claim(i, "a@x"));
+        let remote = edit(&local, "b@x", |i| i.deleted = true);
+
+        assert!(lost(&local, &remote).unwrap().deleted);
+    }
+
+    #[test]
+    fn test_other_editors_claim_is_not_ours_to_lose() {
+        let base = make_issue("st-a");
+        // We edited the issue last, but b@x holds the claim
+        let claimed = edit(&base, "b@x", |i| claim(i, "b@x"));
+        let local = edit(&claimed, "a@x", |i| i.priority = Priority::High);
+        let remote = edit(&claimed, "b@x", release);
+
+        assert!(lost(&local, &remote).is_none());
+    }
+
+    #[test]
+    fn test_quarantine_edges_keeps_valid_graph() {
+        let issues: HashMap<String, Issue> = ["st-a", "st-b", "st-c"]
+            .iter()
+            .map(|id| (id.to_string(), make_issue(id)))
+            .collect();
+        let local_edges = vec![make_edge("st-a", "st-b", EdgeType::DependsOn)];
+        let mut edges_to_save = vec![make_edge("st-b", "st-c", EdgeType::DependsOn)];
+
+        let quarantined =
+            quarantine_edges(&issues, &[], &local_edges, &mut edges_to_save, "a@x", 5);
+        assert!(quarantined.is_empty());
+        assert_eq!(
+            edges_to_save,
+            vec![make_edge("st-b", "st-c", EdgeType::DependsOn)]
+        );
+    }
+
+    #[test]
+    fn test_quarantine_edges_breaks_merged_cycle() {
+        let issues: HashMap<String, Issue> = ["st-a", "st-b"]
+            .iter()
+            .map(|id| (id.to_string(), make_issue(id)))
+            .collect();
+        // Each side added one half of the cycle
+        let local_edges = vec![make_edge("st-a", "st-b", EdgeType::DependsOn)];
+        let mut edges_to_save = vec![make_edge("st-b", "st-a", EdgeType::DependsOn)];
+
+        let quarantined =
+            quarantine_edges(&issues, &[], &local_edges, &mut edges_to_save, "a@x", 5);
+        assert_eq!(quarantined.len(), 1);
+        let edge = &quarantined[0];
+        assert!(edge.quarantine.is_some());
+        assert_eq!(edge.lamport, 6);
+        // The quarantined copy replaces any pending write of the same edge
+        assert_eq!(edges_to_save.iter().filter(|e| e.same_key(edge)).count(), 1);
+        assert!(edges_to_save.iter().any(|e| e.quarantine.is_some()));
+    }
+
+    #[test]
+    fn test_quarantine_edges_sets_aside_edge_to_deleted_issue() {
+        let issues: HashMap<String, Issue> = ["st-a", "st-b"]
+            .iter()
+            .map(|id| (id.to_string(), make_issue(id)))
+            .collect();
+        let local_edges = vec![make_edge("st-a", "st-b", EdgeType::DependsOn)];
+        let deleted = edit(&issues["st-b"], "b@x", |i| i.deleted = true);
+        let mut edges_to_save = Vec::new();
+
+        let quarantined = quarantine_edges(
+            &issues,
+            &[deleted],
+            &local_edges,
+            &mut edges_to_save,
+            "a@x",
+            5,
+        );
+        assert_eq!(quarantined.len(), 1);
+        assert!(quarantined[0].same_key(&make_edge("st-a", "st-b", EdgeType::DependsOn)));
+        assert_eq!(edges_to_save, quarantined);
+    }
+
+    #[test]
+    fn test_pull_fast_forwards_then_merges_diverged_history() {
+        let (_dir, a, b, id) = clones();
+
+        let changes = update::Changes {
+            title: Some("Renamed".to_string()),
+            ..Default::default()
+        };
+        update::update(&a, &id, changes).unwrap();
+        push::push(&a, "origin").unwrap();
+
+        // Nothing local to keep, so b takes a's commit as its own head
+        let summary = pull(&b, "origin", false).unwrap();
+        assert_eq!(summary.issues_updated, 1);
+        assert_eq!(head(&b).id(), head(&a).id());
+
+        let changes = update::Changes {
+            priority: Some(Priority::High),
+            ..Default::default()
+        };
+        update::update(&a, &id, changes).unwrap();
+        push::push(&a, "origin").unwrap();
+        let changes = update::Changes {
+            issue_type: Some(IssueType::Bug),
+            ..Default::default()
+        };
+        update::update(&b, &id, changes).unwrap();
+        let (local, remote) = (head(&b).id(), head(&a).id());
+
+        pull(&b, "origin", false).unwrap();
+        let merge = head(&b);
+        assert_eq!(merge.parent_ids().collect::<Vec<_>>(), vec![local, remote]);
+        let issue = snapshot::load_issue(&b, &id).unwrap();
+        assert_eq!(issue.title, "Renamed");
+        assert_eq!(issue.priority, Priority::High);
+        assert_eq!(issue.issue_type, IssueType::Bug);
+
+        // The merge commit makes b's next push a fast-forward
+        push::push(&b, "origin").unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_pull_writes_nothing() {
+        let (_dir, a, b, id) = clones();
+        let changes = update::Changes {
+            title: Some("Renamed".to_string()),
+            ..Default::default()
+        };
+        update::update(&a, &id, changes).unwrap();
+        push::push(&a, "origin").unwrap();
+
+        // Fetch first, so the tracking ref is the only thing a dry run may move
+        fetch::fetch(&b, "origin").unwrap();
+        let before = refs(&b);
+        let summary = pull(&b, "origin", true).unwrap();
+        assert_eq!(summary.issues_updated, 1);
+        assert_eq!(refs(&b), before);
+
+        let changes = update::Changes {
+            priority: Some(Priority::High),
+            ..Default::default()
+        };
+        update::update(&b, &id, changes).unwrap();
+        let before = refs(&b);
+        let summary = pull(&b, "origin", true).unwrap();
+        assert_eq!(summary.issues_updated, 1);
+        assert_eq!(refs(&b), before);
+        assert_eq!(snapshot::load_issue(&b, &id).unwrap().title, "Original");
+    }
 }
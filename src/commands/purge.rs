@@ -6,6 +6,14 @@ use crate::commands::export;
 use crate::error::Error;
 use crate::snapshot;
 
+/// Remove the snapshot ref. Objects are left for git gc.
+pub fn purge(repo: &Repository) -> Result<(), Error> {
+    if !snapshot::is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+    snapshot::delete_snapshot(repo)
+}
+
 pub fn run(yes: bool) -> Result<(), Error> {
     let repo = Repository::discover(".")?;
 
@@ -14,7 +22,7 @@ pub fn run(yes: bool) -> Result<(), Error> {
     }
 
     eprintln!("Exporting current data as backup...");
-    export::run(None)?;
+    export::run(None, None)?;
     eprintln!();
 
     if !yes {
@@ -30,7 +38,7 @@ pub fn run(yes: bool) -> Result<(), Error> {
         }
     }
 
-    snapshot::delete_snapshot(&repo)?;
+    purge(&repo)?;
     eprintln!("Removed refs/sterna/snapshot");
 
     eprintln!("Purge complete. Orphaned blobs will be cleaned by git gc.");
@@ -1,13 +1,32 @@
-use git2::Repository;
+use std::cell::RefCell;
 
+use git2::{ErrorCode, PushOptions, RemoteCallbacks, Repository};
+
+use crate::commands::{pull, remote};
 use crate::error::Error;
 use crate::snapshot;
+use crate::storage;
 
-pub fn run(remote: Option<String>) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
-    let remote_name = remote.unwrap_or_else(|| "origin".to_string());
+/// How many times a rejected push is retried after merging the remote
+pub const DEFAULT_RETRIES: usize = 3;
+
+/// What was pushed to the remote
+#[derive(Debug, Clone)]
+pub struct PushSummary {
+    pub commit_id: String,
+    pub issues: usize,
+    pub edges: usize,
+    /// Push attempts made, including the successful one
+    pub attempts: usize,
+    /// Merges of the remote run after each rejected attempt
+    pub merges: Vec<pull::PullSummary>,
+}
 
-    if !snapshot::is_initialized(&repo) {
+/// Push the local snapshot ref to a remote (name, URL or path) once. A
+/// rejected update (e.g. a non-fast-forward) is reported as
+/// `Error::PushRejected`.
+pub fn push(repo: &Repository, remote_name: &str) -> Result<PushSummary, Error> {
+    if !snapshot::is_initialized(repo) {
         return Err(Error::NotInitialized);
     }
 
@@ -15,19 +34,174 @@ pub fn run(remote: Option<String>) -> Result<(), Error> {
     let commit = reference.peel_to_commit()?;
     let commit_id = commit.id().to_string();
 
-    let issues = snapshot::load_issues(&repo)?;
-    let edges = snapshot::load_edges(&repo)?;
+    let issues = snapshot::load_issues(repo)?;
+    let edges = snapshot::load_edges(repo)?;
 
-    let mut git_remote = repo.find_remote(&remote_name)?;
-    git_remote.push(&["refs/sterna/snapshot:refs/sterna/snapshot"], None)?;
+    // The remote reports per-ref rejections through this callback rather than an error
+    let rejection: RefCell<Option<String>> = RefCell::new(None);
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.push_update_reference(|_refname, status| {
+        if let Some(message) = status {
+            *rejection.borrow_mut() = Some(message.to_string());
+        }
+        Ok(())
+    });
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+
+    let mut git_remote = storage::find_remote(repo, remote_name)?;
+    let rejected = |reason: String| Error::PushRejected {
+        remote: remote_name.to_string(),
+        reason,
+    };
+    match git_remote.push(&[remote::PUSH_REFSPEC], Some(&mut options)) {
+        Ok(()) => {}
+        // Local transports refuse non-fast-forwards up front
+        Err(e) if e.code() == ErrorCode::NotFastForward => {
+            return Err(rejected(e.message().to_string()));
+        }
+        Err(e) => return Err(e.into()),
+    }
+    drop(options);
+    if let Some(reason) = rejection.into_inner() {
+        return Err(rejected(reason));
+    }
+    // The remote now has our snapshot; keep its tracking ref in step
+    if repo.find_remote(remote_name).is_ok() {
+        repo.reference(
+            &snapshot::remote_ref(remote_name),
+            commit.id(),
+            true,
+            &format!("st push {remote_name}"),
+        )?;
+    }
+
+    Ok(PushSummary {
+        commit_id,
+        issues: issues.len(),
+        edges: edges.len(),
+        attempts: 1,
+        merges: Vec::new(),
+    })
+}
+
+/// Push, and on rejection pull (fetch and merge) the remote and try again,
+/// up to `retries` more times
+pub fn push_with_retry(
+    repo: &Repository,
+    remote_name: &str,
+    retries: usize,
+) -> Result<PushSummary, Error> {
+    let mut attempts = 0;
+    let mut merges = Vec::new();
+    loop {
+        attempts += 1;
+        match push(repo, remote_name) {
+            Ok(summary) => {
+                return Ok(PushSummary {
+                    attempts,
+                    merges,
+                    ..summary
+                });
+            }
+            Err(Error::PushRejected { reason, .. }) if attempts > retries => {
+                return Err(Error::PushGaveUp {
+                    remote: remote_name.to_string(),
+                    attempts,
+                    reason,
+                });
+            }
+            Err(Error::PushRejected { .. }) => {
+                merges.push(pull::pull(repo, remote_name, false)?);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub fn run(remote: Option<String>, retries: usize, strict: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let remote_name = remote.unwrap_or_else(|| "origin".to_string());
+
+    let summary = push_with_retry(&repo, &remote_name, retries)?;
+
+    for merge in &summary.merges {
+        pull::print_summary(&remote_name, merge, false);
+    }
 
     eprintln!(
-        "Pushed snapshot ({}) with {} issues, {} edges to {}",
-        &commit_id[..7],
-        issues.len(),
-        edges.len(),
-        remote_name
+        "Pushed snapshot ({}) with {} issues, {} edges to {}{}",
+        &summary.commit_id[..7],
+        summary.issues,
+        summary.edges,
+        remote_name,
+        if summary.attempts > 1 {
+            format!(" (merged remote changes, {} attempts)", summary.attempts)
+        } else {
+            String::new()
+        }
     );
 
+    let lost_claims: usize = summary.merges.iter().map(|m| m.lost_claims.len()).sum();
+    if strict && lost_claims > 0 {
+        return Err(Error::LostClaims(lost_claims));
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::update;
+    use crate::testing::{clones, head};
+    use crate::types::Priority;
+
+    fn remote_head(repo: &Repository) -> git2::Oid {
+        let origin = Repository::open(repo.find_remote("origin").unwrap().url().unwrap()).unwrap();
+        origin.refname_to_id("refs/sterna/snapshot").unwrap()
+    }
+
+    /// Edit the issue on both clones and push `a`, so `b` is behind
+    fn diverge(a: &Repository, b: &Repository, id: &str) {
+        let changes = update::Changes {
+            title: Some("Renamed".to_string()),
+            ..Default::default()
+        };
+        update::update(a, id, changes).unwrap();
+        push(a, "origin").unwrap();
+        let changes = update::Changes {
+            priority: Some(Priority::High),
+            ..Default::default()
+        };
+        update::update(b, id, changes).unwrap();
+    }
+
+    #[test]
+    fn test_rejected_push_merges_and_retries() {
+        let (_dir, a, b, id) = clones();
+        diverge(&a, &b, &id);
+
+        assert!(matches!(
+            push(&b, "origin"),
+            Err(Error::PushRejected { .. })
+        ));
+        let summary = push_with_retry(&b, "origin", DEFAULT_RETRIES).unwrap();
+        assert_eq!(summary.attempts, 2);
+        assert_eq!(summary.merges.len(), 1);
+        assert_eq!(summary.merges[0].issues_updated, 1);
+        assert_eq!(remote_head(&b), head(&b).id());
+        assert_eq!(snapshot::load_issue(&b, &id).unwrap().title, "Renamed");
+    }
+
+    #[test]
+    fn test_push_gives_up_after_retries() {
+        let (_dir, a, b, id) = clones();
+        diverge(&a, &b, &id);
+
+        match push_with_retry(&b, "origin", 0) {
+            Err(Error::PushGaveUp { attempts, .. }) => assert_eq!(attempts, 1),
+            other => panic!("expected PushGaveUp, got {other:?}"),
+        }
+        assert_eq!(remote_head(&b), head(&a).id());
+    }
+}
@@ -0,0 +1,104 @@
+use git2::Repository;
+
+use crate::dag;
+use crate::error::Error;
+use crate::snapshot;
+use crate::storage;
+use crate::types::Edge;
+
+/// Edges a pull set aside, sorted by name
+pub fn list(repo: &Repository) -> Result<Vec<Edge>, Error> {
+    let mut edges: Vec<Edge> = snapshot::load_all_edges(repo)?
+        .into_iter()
+        .filter(|e| !e.deleted && e.quarantine.is_some())
+        .collect();
+    edges.sort_by_key(snapshot::edge_name);
+    Ok(edges)
+}
+
+/// Resolve a quarantined edge by name or unique name prefix: `restore` makes
+/// it live again if its issues exist and it no longer closes a cycle,
+/// otherwise it is removed with a tombstone. Returns the resolved edge.
+pub fn resolve(repo: &Repository, name_prefix: &str, restore: bool) -> Result<Edge, Error> {
+    let quarantined = list(repo)?;
+    let matches: Vec<&Edge> = quarantined
+        .iter()
+        .filter(|e| snapshot::edge_name(e).starts_with(name_prefix))
+        .collect();
+    let mut edge = match matches.as_slice() {
+        [] => return Err(Error::NotQuarantined(name_prefix.to_string())),
+        [edge] => (*edge).clone(),
+        _ => {
+            return Err(Error::AmbiguousId(
+                name_prefix.to_string(),
+                matches.iter().map(|e| snapshot::edge_name(e)).collect(),
+            ));
+        }
+    };
+    let name = snapshot::edge_name(&edge);
+
+    edge.quarantine = None;
+    if restore {
+        let issues = snapshot::load_issues(repo)?;
+        for id in [&edge.source, &edge.target] {
+            if !issues.contains_key(id) {
+                return Err(Error::NotFound(id.clone()));
+            }
+        }
+        let edges = snapshot::load_edges(repo)?;
+        if dag::would_create_cycle(&edges, &edge.source, &edge.target, edge.edge_type) {
+            return Err(Error::WouldCreateCycle(edge.source, edge.target));
+        }
+    } else {
+        edge.deleted = true;
+    }
+    edge.record_edit(storage::get_editor(repo)?, snapshot::clock(repo)?);
+
+    let action = if restore { "Restore" } else { "Drop" };
+    snapshot::save_edge(repo, &edge, &format!("{action} quarantined edge {name}"))?;
+    Ok(edge)
+}
+
+pub fn run(json: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let edges = list(&repo)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&edges)?);
+        return Ok(());
+    }
+
+    if edges.is_empty() {
+        println!("No quarantined edges");
+        return Ok(());
+    }
+    for edge in &edges {
+        println!("{}", snapshot::edge_name(edge));
+        println!(
+            "    {} {} {}: {}",
+            edge.source,
+            edge.edge_type.as_str(),
+            edge.target,
+            edge.quarantine.as_deref().unwrap_or("")
+        );
+    }
+    Ok(())
+}
+
+pub fn run_resolve(name_prefix: String, restore: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let edge = resolve(&repo, &name_prefix, restore)?;
+
+    let what = format!(
+        "{} {} {}",
+        edge.source,
+        edge.edge_type.as_str(),
+        edge.target
+    );
+    if restore {
+        println!("Restored edge {what}");
+    } else {
+        println!("Dropped edge {what}");
+    }
+    Ok(())
+}
@@ -1,27 +1,44 @@
 use std::collections::HashMap;
 
-use git2::Repository;
+use git2::{Commit, Repository};
 
 use crate::error::Error;
 use crate::snapshot;
 use crate::types::{Edge, EdgeType, Issue, Status};
 
-pub fn run(json: bool) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
-
-    let all_issues = snapshot::load_issues(&repo)?;
-    let edges = snapshot::load_edges(&repo)?;
+/// Issues that are open, unclaimed and not blocked, sorted by priority.
+/// Reads the snapshot at `at` when given, otherwise the current one.
+pub fn ready(repo: &Repository, at: Option<&Commit>) -> Result<Vec<Issue>, Error> {
+    let (all_issues, edges) = match at {
+        Some(commit) => (
+            snapshot::load_issues_at(repo, commit)?,
+            snapshot::load_edges_at(repo, commit)?,
+        ),
+        None => (snapshot::load_issues(repo)?, snapshot::load_edges(repo)?),
+    };
 
     let mut ready_issues: Vec<Issue> = Vec::new();
     for issue in all_issues.values() {
         // Ready = open AND not claimed AND not blocked
-        if issue.status == Status::Open && !issue.claimed
-            && !is_blocked(&issue.id, &edges, &all_issues) {
-                ready_issues.push(issue.clone());
-            }
+        if issue.status == Status::Open
+            && !issue.claimed
+            && !is_blocked(&issue.id, &edges, &all_issues)
+        {
+            ready_issues.push(issue.clone());
+        }
     }
 
     ready_issues.sort_by_key(|i| i.priority);
+    Ok(ready_issues)
+}
+
+pub fn run(at: Option<String>, json: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let at = at
+        .map(|spec| snapshot::resolve_at(&repo, &spec))
+        .transpose()?;
+
+    let ready_issues = ready(&repo, at.as_ref())?;
 
     if json {
         println!("{}", serde_json::to_string_pretty(&ready_issues)?);
@@ -50,32 +67,29 @@ fn is_blocked(issue_id: &str, edges: &[Edge], issues: &HashMap<String, Issue>) -
         match edge.edge_type {
             EdgeType::DependsOn => {
                 // If this issue depends on another, check if target is closed
-                if edge.source == issue_id {
-                    if let Some(target) = issues.get(&edge.target) {
-                        if target.status != Status::Closed {
-                            return true;
-                        }
-                    }
+                if edge.source == issue_id
+                    && let Some(target) = issues.get(&edge.target)
+                    && target.status != Status::Closed
+                {
+                    return true;
                 }
             }
             EdgeType::Blocks => {
                 // If another issue blocks this one, check if source is closed
-                if edge.target == issue_id {
-                    if let Some(source) = issues.get(&edge.source) {
-                        if source.status != Status::Closed {
-                            return true;
-                        }
-                    }
+                if edge.target == issue_id
+                    && let Some(source) = issues.get(&edge.source)
+                    && source.status != Status::Closed
+                {
+                    return true;
                 }
             }
             EdgeType::ParentChild => {
                 // Child is blocked if parent is not closed
-                if edge.source == issue_id {
-                    if let Some(parent) = issues.get(&edge.target) {
-                        if parent.status != Status::Closed {
-                            return true;
-                        }
-                    }
+                if edge.source == issue_id
+                    && let Some(parent) = issues.get(&edge.target)
+                    && parent.status != Status::Closed
+                {
+                    return true;
                 }
             }
             // RelatesTo and Duplicates don't block
@@ -0,0 +1,222 @@
+use git2::Repository;
+
+use crate::error::Error;
+use crate::snapshot;
+
+/// Push refspec that makes plain `git push <remote>` publish the snapshot
+pub const PUSH_REFSPEC: &str = "refs/sterna/snapshot:refs/sterna/snapshot";
+
+/// Push refspec added alongside ours when a remote had none. Once any push
+/// refspec is configured, `git push` without arguments pushes only those, so
+/// this keeps it pushing the current branch.
+const HEAD_REFSPEC: &str = "HEAD";
+
+/// Refspecs written to or removed from a remote's git config
+#[derive(Debug, Clone, Default)]
+pub struct RefspecChanges {
+    pub fetch: Vec<String>,
+    pub push: Vec<String>,
+}
+
+impl RefspecChanges {
+    pub fn is_empty(&self) -> bool {
+        self.fetch.is_empty() && self.push.is_empty()
+    }
+}
+
+/// Fetch refspec that makes plain `git fetch <remote>` update the tracking ref
+pub fn fetch_refspec(remote_name: &str) -> String {
+    format!(
+        "+{}:{}",
+        snapshot::SNAPSHOT_REF,
+        snapshot::remote_ref(remote_name)
+    )
+}
+
+/// Add the snapshot fetch and push refspecs to a configured remote, so that
+/// plain `git fetch` and `git push` carry the snapshot along. Refspecs that
+/// are already present are left alone.
+pub fn setup(repo: &Repository, remote_name: &str) -> Result<RefspecChanges, Error> {
+    let remote = repo.find_remote(remote_name)?;
+    let fetch_specs = refspecs(remote.fetch_refspecs()?);
+    let push_specs = refspecs(remote.push_refspecs()?);
+
+    let mut added = RefspecChanges::default();
+    let fetch = fetch_refspec(remote_name);
+    if !fetch_specs.contains(&fetch) {
+        repo.remote_add_fetch(remote_name, &fetch)?;
+        added.fetch.push(fetch);
+    }
+    if !push_specs.iter().any(|s| s == PUSH_REFSPEC) {
+        if push_specs.is_empty() {
+            repo.remote_add_push(remote_name, HEAD_REFSPEC)?;
+            added.push.push(HEAD_REFSPEC.to_string());
+        }
+        repo.remote_add_push(remote_name, PUSH_REFSPEC)?;
+        added.push.push(PUSH_REFSPEC.to_string());
+    }
+    Ok(added)
+}
+
+/// Undo `setup`: remove the snapshot refspecs, and the `HEAD` push refspec
+/// when it is the only one left. The tracking ref itself is kept.
+pub fn remove(repo: &Repository, remote_name: &str) -> Result<RefspecChanges, Error> {
+    let remote = repo.find_remote(remote_name)?;
+    let fetch_specs = refspecs(remote.fetch_refspecs()?);
+    let push_specs = refspecs(remote.push_refspecs()?);
+    let mut config = repo.config()?;
+
+    let mut removed = RefspecChanges::default();
+    let fetch = fetch_refspec(remote_name);
+    if fetch_specs.contains(&fetch) {
+        config.remove_multivar(&format!("remote.{remote_name}.fetch"), &exact(&fetch))?;
+        removed.fetch.push(fetch);
+    }
+
+    let push_key = format!("remote.{remote_name}.push");
+    if push_specs.iter().any(|s| s == PUSH_REFSPEC) {
+        config.remove_multivar(&push_key, &exact(PUSH_REFSPEC))?;
+        removed.push.push(PUSH_REFSPEC.to_string());
+
+        let rest: Vec<&String> = push_specs.iter().filter(|s| *s != PUSH_REFSPEC).collect();
+        if rest == [HEAD_REFSPEC] {
+            config.remove_multivar(&push_key, &exact(HEAD_REFSPEC))?;
+            removed.push.push(HEAD_REFSPEC.to_string());
+        }
+    }
+    Ok(removed)
+}
+
+fn refspecs(specs: git2::string_array::StringArray) -> Vec<String> {
+    specs.iter().flatten().map(str::to_string).collect()
+}
+
+/// Regex matching exactly `value`, for removing one value of a multivar
+fn exact(value: &str) -> String {
+    let mut pattern = String::from("^");
+    for c in value.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('$');
+    pattern
+}
+
+pub fn run_setup(remote: Option<String>, remove_refspecs: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let remote_name = remote.unwrap_or_else(|| "origin".to_string());
+
+    let (changes, verb) = if remove_refspecs {
+        (remove(&repo, &remote_name)?, "Removed")
+    } else {
+        (setup(&repo, &remote_name)?, "Added")
+    };
+
+    if changes.is_empty() {
+        if remove_refspecs {
+            println!("{remote_name} has no Sterna refspecs");
+        } else {
+            println!("{remote_name} is already set up for Sterna");
+        }
+        return Ok(());
+    }
+    for spec in &changes.fetch {
+        println!("{verb} fetch refspec {spec}");
+    }
+    for spec in &changes.push {
+        println!("{verb} push refspec {spec}");
+    }
+    if !remove_refspecs {
+        println!("git fetch and git push {remote_name} now carry the Sterna snapshot");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{fetch, pull, push, update};
+    use crate::testing::{clones, head};
+
+    fn push_specs(repo: &Repository) -> Vec<String> {
+        refspecs(repo.find_remote("origin").unwrap().push_refspecs().unwrap())
+    }
+
+    fn retitle(repo: &Repository, id: &str, title: &str) {
+        let changes = update::Changes {
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        update::update(repo, id, changes).unwrap();
+    }
+
+    #[test]
+    fn test_setup_and_remove_refspecs() {
+        let (_dir, _a, b, _id) = clones();
+
+        let added = setup(&b, "origin").unwrap();
+        assert_eq!(added.fetch, vec![fetch_refspec("origin")]);
+        assert_eq!(push_specs(&b), vec![HEAD_REFSPEC, PUSH_REFSPEC]);
+        assert!(setup(&b, "origin").unwrap().is_empty());
+
+        let removed = remove(&b, "origin").unwrap();
+        assert_eq!(removed.push, vec![PUSH_REFSPEC, HEAD_REFSPEC]);
+        assert!(push_specs(&b).is_empty());
+        let fetch_specs = refspecs(b.find_remote("origin").unwrap().fetch_refspecs().unwrap());
+        assert!(!fetch_specs.contains(&fetch_refspec("origin")));
+        assert!(remove(&b, "origin").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_setup_keeps_existing_push_refspecs() {
+        let (_dir, _a, b, _id) = clones();
+        b.remote_add_push("origin", "refs/heads/main:refs/heads/main")
+            .unwrap();
+
+        setup(&b, "origin").unwrap();
+        assert_eq!(
+            push_specs(&b),
+            vec!["refs/heads/main:refs/heads/main", PUSH_REFSPEC]
+        );
+        remove(&b, "origin").unwrap();
+        assert_eq!(push_specs(&b), vec!["refs/heads/main:refs/heads/main"]);
+    }
+
+    #[test]
+    fn test_fetch_after_setup_leaves_local_snapshot() {
+        let (_dir, a, b, id) = clones();
+        setup(&b, "origin").unwrap();
+        retitle(&a, &id, "Remote");
+        push::push(&a, "origin").unwrap();
+        retitle(&b, &id, "Local");
+        let local = head(&b).id();
+
+        fetch::fetch(&b, "origin").unwrap();
+        assert_eq!(head(&b).id(), local);
+        assert_eq!(
+            snapshot::remote_commit(&b, "origin").unwrap().id(),
+            head(&a).id()
+        );
+    }
+
+    #[test]
+    fn test_pull_falls_back_to_tracking_ref() {
+        let (dir, a, b, id) = clones();
+        retitle(&a, &id, "Renamed");
+        push::push(&a, "origin").unwrap();
+        fetch::fetch(&b, "origin").unwrap();
+        let gone = dir.path().join("gone.git");
+        b.remote_set_url("origin", gone.to_str().unwrap()).unwrap();
+
+        let summary = pull::pull(&b, "origin", false).unwrap();
+        assert!(summary.fetch_error.is_some());
+        assert_eq!(snapshot::load_issue(&b, &id).unwrap().title, "Renamed");
+    }
+
+    #[test]
+    fn test_exact_escapes_refspec() {
+        assert_eq!(exact("+a.b:c*"), r"^\+a\.b:c\*$");
+    }
+}
@@ -3,13 +3,13 @@ use git2::Repository;
 use crate::error::Error;
 use crate::snapshot;
 use crate::storage;
-use crate::types::Status;
+use crate::types::{Issue, Status};
 
-pub fn run(id_prefix: String, reason: Option<String>) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
-
-    let id = snapshot::find_issue_id(&repo, &id_prefix)?;
-    let mut issue = snapshot::load_issue(&repo, &id)?;
+/// Reopen a closed issue
+pub fn reopen(repo: &Repository, id_prefix: &str, reason: Option<String>) -> Result<Issue, Error> {
+    let id = snapshot::find_issue_id(repo, id_prefix)?;
+    let before = snapshot::load_issue(repo, &id)?;
+    let mut issue = before.clone();
 
     if issue.status != Status::Closed {
         return Err(Error::NotClosed(id));
@@ -17,12 +17,18 @@ pub fn run(id_prefix: String, reason: Option<String>) -> Result<(), Error> {
 
     issue.status = Status::Open;
     issue.reason = reason;
-    issue.lamport += 1;
-    issue.updated_at = chrono::Utc::now().timestamp();
-    issue.editor = storage::get_editor()?;
+    issue.record_edit(&before, storage::get_editor(repo)?, snapshot::clock(repo)?);
+
+    snapshot::save_issue(repo, &issue, &format!("Reopen issue {id}"))?;
+
+    Ok(issue)
+}
+
+pub fn run(id_prefix: String, reason: Option<String>) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
 
-    snapshot::save_issue(&repo, &issue, &format!("Reopen issue {id}"))?;
+    let issue = reopen(&repo, &id_prefix, reason)?;
 
-    println!("Reopened {id}");
+    println!("Reopened {}", issue.id);
     Ok(())
 }
@@ -0,0 +1,238 @@
+use std::collections::BTreeSet;
+
+use git2::{Repository, Tree};
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::snapshot;
+use crate::storage;
+use crate::types::Issue;
+
+/// Entry names that one side changed since the merge base
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SideChanges {
+    pub issues: Vec<String>,
+    pub edges: Vec<String>,
+}
+
+/// Local snapshot compared with the last fetched remote snapshot
+#[derive(Serialize, Debug, Clone)]
+pub struct RemoteStatus {
+    pub name: String,
+    pub commit_id: String,
+    /// Local snapshot commits the remote does not have yet (unpushed)
+    pub ahead: usize,
+    /// Fetched remote snapshot commits not merged locally yet
+    pub behind: usize,
+    pub local_changes: SideChanges,
+    pub remote_changes: SideChanges,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StatusReport {
+    pub commit_id: String,
+    pub issues: usize,
+    pub edges: usize,
+    /// `None` until the remote has been fetched
+    pub remote: Option<RemoteStatus>,
+    /// Open claims the current editor holds
+    pub claims: Vec<Issue>,
+}
+
+/// Compare the local snapshot with the tracking ref of `remote_name`.
+/// Nothing is fetched; run `fetch` first for an up-to-date view.
+pub fn status(repo: &Repository, remote_name: &str) -> Result<StatusReport, Error> {
+    if !snapshot::is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let local = snapshot::get_snapshot_commit(repo)?;
+    let local_tree = local.tree()?;
+
+    let remote = match snapshot::remote_commit(repo, remote_name) {
+        Some(remote) => {
+            let (ahead, behind) = repo.graph_ahead_behind(local.id(), remote.id())?;
+            let base_tree = match repo.merge_base(local.id(), remote.id()) {
+                Ok(base) => Some(repo.find_commit(base)?.tree()?),
+                Err(e) if e.code() == git2::ErrorCode::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+            let remote_tree = remote.tree()?;
+            Some(RemoteStatus {
+                name: remote_name.to_string(),
+                commit_id: remote.id().to_string(),
+                ahead,
+                behind,
+                local_changes: side_changes(repo, base_tree.as_ref(), &local_tree)?,
+                remote_changes: side_changes(repo, base_tree.as_ref(), &remote_tree)?,
+            })
+        }
+        None => None,
+    };
+
+    let editor = storage::get_editor(repo)?;
+    let issues = snapshot::load_issues(repo)?;
+    let mut claims: Vec<Issue> = issues
+        .values()
+        .filter(|i| i.claimed && i.claimed_by.as_deref() == Some(editor.as_str()))
+        .cloned()
+        .collect();
+    claims.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(StatusReport {
+        commit_id: local.id().to_string(),
+        issues: issues.len(),
+        edges: snapshot::load_edges(repo)?.len(),
+        remote,
+        claims,
+    })
+}
+
+fn side_changes(repo: &Repository, base: Option<&Tree>, side: &Tree) -> Result<SideChanges, Error> {
+    Ok(SideChanges {
+        issues: changed_entries(repo, base, side, "issues")?,
+        edges: changed_entries(repo, base, side, "edges")?,
+    })
+}
+
+/// Names in the `name` subtree whose blob differs between `base` and `side`
+fn changed_entries(
+    repo: &Repository,
+    base: Option<&Tree>,
+    side: &Tree,
+    name: &str,
+) -> Result<Vec<String>, Error> {
+    let side = snapshot::get_subtree(repo, side, name)?;
+    let base = base
+        .map(|tree| snapshot::get_subtree(repo, tree, name))
+        .transpose()?;
+
+    let mut names = BTreeSet::new();
+    for entry in side.iter() {
+        let entry_name = entry.name().unwrap_or("");
+        let before = base.as_ref().and_then(|b| b.get_name(entry_name));
+        if before.map(|e| e.id()) != Some(entry.id()) {
+            names.insert(entry_name.to_string());
+        }
+    }
+    for entry in base.iter().flat_map(|b| b.iter()) {
+        let entry_name = entry.name().unwrap_or("");
+        if side.get_name(entry_name).is_none() {
+            names.insert(entry_name.to_string());
+        }
+    }
+    Ok(names.into_iter().collect())
+}
+
+pub fn run(remote: Option<String>, json: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let remote_name = remote.unwrap_or_else(|| "origin".to_string());
+
+    let report = status(&repo, &remote_name)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "Snapshot {}: {} issues, {} edges",
+        &report.commit_id[..7],
+        report.issues,
+        report.edges
+    );
+    match &report.remote {
+        Some(remote) => {
+            println!(
+                "{} (fetched {}): {} unpushed commits, {} commits behind",
+                remote.name,
+                &remote.commit_id[..7],
+                remote.ahead,
+                remote.behind
+            );
+            print_changes("local changes: ", &remote.local_changes);
+            print_changes("remote changes:", &remote.remote_changes);
+        }
+        None => println!("{remote_name}: not fetched (run st fetch {remote_name})"),
+    }
+
+    if report.claims.is_empty() {
+        println!("No claims");
+    } else {
+        println!("Your claims:");
+        for issue in &report.claims {
+            println!("  {:<12} {}", issue.id, issue.title);
+        }
+    }
+    Ok(())
+}
+
+fn print_changes(label: &str, changes: &SideChanges) {
+    println!(
+        "  {label} {} issues, {} edges",
+        changes.issues.len(),
+        changes.edges.len()
+    );
+    for name in changes.issues.iter().chain(&changes.edges) {
+        println!("    {name}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{claim, create, fetch, pull, push, update};
+    use crate::testing::clones;
+    use crate::types::{IssueType, Priority};
+
+    #[test]
+    fn test_status_counts_both_sides_since_last_fetch() {
+        let (_dir, a, b, id) = clones();
+        let changes = update::Changes {
+            title: Some("Renamed".to_string()),
+            ..Default::default()
+        };
+        update::update(&a, &id, changes).unwrap();
+        push::push(&a, "origin").unwrap();
+        let other = create::create(
+            &b,
+            "Other".to_string(),
+            String::new(),
+            Priority::Medium,
+            IssueType::Task,
+            Vec::new(),
+        )
+        .unwrap();
+
+        // a's push is not visible until fetched
+        let remote = status(&b, "origin").unwrap().remote.unwrap();
+        assert_eq!((remote.ahead, remote.behind), (1, 0));
+
+        fetch::fetch(&b, "origin").unwrap();
+        let remote = status(&b, "origin").unwrap().remote.unwrap();
+        assert_eq!((remote.ahead, remote.behind), (1, 1));
+        assert_eq!(remote.local_changes.issues, vec![other.id]);
+        assert_eq!(remote.remote_changes.issues, vec![id]);
+
+        assert!(status(&b, "mirror").unwrap().remote.is_none());
+    }
+
+    #[test]
+    fn test_status_lists_only_own_claims() {
+        let (_dir, a, b, id) = clones();
+        claim::claim(&a, &id, None).unwrap();
+        push::push(&a, "origin").unwrap();
+        pull::pull(&b, "origin", false).unwrap();
+        // b editing a's claimed issue does not make the claim b's
+        let changes = update::Changes {
+            priority: Some(Priority::High),
+            ..Default::default()
+        };
+        update::update(&b, &id, changes).unwrap();
+
+        assert!(status(&b, "origin").unwrap().claims.is_empty());
+        let claims = status(&a, "origin").unwrap().claims;
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].id, id);
+    }
+}
@@ -1,8 +1,60 @@
+use git2::Repository;
+
 use crate::commands::{pull, push};
 use crate::error::Error;
 
-pub fn run(remote: Option<String>) -> Result<(), Error> {
-    pull::run(remote.clone())?;
-    push::run(remote)?;
+pub fn run(remote: Option<String>, retries: usize, all: bool, strict: bool) -> Result<(), Error> {
+    let mut lost_claims = 0;
+    if all {
+        run_all(retries, strict, &mut lost_claims)?;
+    } else {
+        lost(
+            pull::run(remote.clone(), strict, false, false),
+            &mut lost_claims,
+        )?;
+        // Another clone may push between our pull and push; the retry merges it in
+        lost(push::run(remote, retries, strict), &mut lost_claims)?;
+    }
+    if lost_claims > 0 {
+        return Err(Error::LostClaims(lost_claims));
+    }
     Ok(())
 }
+
+/// Pull from every configured remote first, then push to each, so all of
+/// them end up with the union of their changes
+fn run_all(retries: usize, strict: bool, lost_claims: &mut usize) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+    let remotes: Vec<String> = repo.remotes()?.iter().flatten().map(String::from).collect();
+    if remotes.is_empty() {
+        eprintln!("No remotes configured");
+        return Ok(());
+    }
+
+    for remote in &remotes {
+        match pull::run(Some(remote.clone()), strict, false, false) {
+            // A new mirror has nothing to pull yet; the push below fills it
+            Err(Error::RemoteNotInitialized(_)) => {}
+            result => lost(result, lost_claims)?,
+        }
+    }
+    for remote in &remotes {
+        lost(
+            push::run(Some(remote.clone()), retries, strict),
+            lost_claims,
+        )?;
+    }
+    Ok(())
+}
+
+/// Count a `--strict` lost-claims failure and carry on, so the rest of the
+/// sync still runs; any other error stops it
+fn lost(result: Result<(), Error>, lost_claims: &mut usize) -> Result<(), Error> {
+    match result {
+        Err(Error::LostClaims(n)) => {
+            *lost_claims += n;
+            Ok(())
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,315 @@
+use git2::Repository;
+
+use crate::error::Error;
+use crate::snapshot::{self, Changeset};
+use crate::storage;
+use crate::types::{Edge, FieldChange};
+
+/// A snapshot commit being reverted
+#[derive(Debug, Clone)]
+pub struct UndoneCommit {
+    pub commit: String,
+    pub message: String,
+}
+
+/// How a single issue is reverted
+#[derive(Debug, Clone)]
+pub struct IssueRevert {
+    pub id: String,
+    /// The issue did not exist before the undone operations
+    pub removed: bool,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Everything an undo reverts
+#[derive(Debug, Clone, Default)]
+pub struct UndoPlan {
+    pub undone: Vec<UndoneCommit>,
+    pub issues: Vec<IssueRevert>,
+    pub edges_restored: Vec<Edge>,
+    pub edges_removed: Vec<Edge>,
+}
+
+/// Revert the last `steps` snapshot operations by writing their inverse as a
+/// new commit. Refuses to cross a pull merge, or a commit a pull brought in
+/// by fast-forwarding, unless `force` is set. With `dry_run` nothing is
+/// written.
+pub fn undo(
+    repo: &Repository,
+    steps: usize,
+    force: bool,
+    dry_run: bool,
+) -> Result<UndoPlan, Error> {
+    let history = snapshot::history(repo)?;
+    if steps == 0 || steps >= history.len() {
+        return Err(Error::NothingToUndo(steps));
+    }
+
+    let current = &history[history.len() - 1];
+    let target = &history[history.len() - 1 - steps];
+
+    let mut plan = UndoPlan::default();
+    for commit in history[history.len() - steps..].iter().rev() {
+        let message = commit.summary().unwrap_or("").to_string();
+        let pulled = commit.parent_count() > 1
+            || message.starts_with("Pull from")
+            || snapshot::is_pulled(repo, commit.id())?;
+        if !force && pulled {
+            return Err(Error::UndoAcrossPull(commit.id().to_string()));
+        }
+        plan.undone.push(UndoneCommit {
+            commit: commit.id().to_string(),
+            message,
+        });
+    }
+
+    let current_issues = snapshot::load_all_issues_at(repo, current)?;
+    let target_issues = snapshot::load_all_issues_at(repo, target)?;
+    let current_edges = snapshot::load_all_edges_at(repo, current)?;
+    let target_edges = snapshot::load_edges_at(repo, target)?;
+
+    let editor = storage::get_editor(repo)?;
+    let clock = snapshot::clock(repo)?;
+    let mut changes = Changeset::default();
+
+    let mut ids: Vec<_> = current_issues.keys().collect();
+    ids.sort();
+    for id in ids {
+        let issue = &current_issues[id];
+        match target_issues.get(id) {
+            Some(old) => {
+                let field_changes = old.changes_from(issue);
+                if field_changes.is_empty() {
+                    continue;
+                }
+                // Restore the old fields as a new edit so it wins on merge
+                let mut restored = old.clone();
+                restored.record_edit(issue, editor.clone(), clock);
+                changes.issues.push(restored);
+                plan.issues.push(IssueRevert {
+                    id: id.clone(),
+                    removed: false,
+                    changes: field_changes,
+                });
+            }
+            None if issue.deleted => {}
+            None => {
+                // Created by the undone operations: delete it with a tombstone
+                let mut tombstone = issue.clone();
+                tombstone.deleted = true;
+                tombstone.record_edit(issue, editor.clone(), clock);
+                changes.issues.push(tombstone);
+                plan.issues.push(IssueRevert {
+                    id: id.clone(),
+                    removed: true,
+                    changes: Vec::new(),
+                });
+            }
+        }
+    }
+
+    // Entries dropped from the tree outright come back as they were
+    let mut missing: Vec<_> = target_issues
+        .keys()
+        .filter(|id| !current_issues.contains_key(*id))
+        .collect();
+    missing.sort();
+    for id in missing {
+        let mut restored = target_issues[id].clone();
+        restored.record_edit(&target_issues[id], editor.clone(), clock);
+        changes.issues.push(restored);
+        plan.issues.push(IssueRevert {
+            id: id.clone(),
+            removed: false,
+            changes: Vec::new(),
+        });
+    }
+
+    // Edges flip between live and tombstoned, with a clock above the current one
+    for edge in &target_edges {
+        let existing = current_edges.iter().find(|e| e.same_key(edge));
+        if existing.is_some_and(Edge::is_live) {
+            continue;
+        }
+        let mut restored = edge.clone();
+        restored.lamport = existing.map_or(edge.lamport, |e| e.lamport);
+        restored.record_edit(editor.clone(), clock);
+        changes.edges.push(restored);
+        plan.edges_restored.push(edge.clone());
+    }
+    for edge in current_edges.iter().filter(|e| e.is_live()) {
+        if target_edges.iter().any(|e| e.same_key(edge)) {
+            continue;
+        }
+        let mut removed = edge.clone();
+        removed.deleted = true;
+        removed.record_edit(editor.clone(), clock);
+        changes.edges.push(removed);
+        plan.edges_removed.push(edge.clone());
+    }
+
+    if !dry_run {
+        snapshot::commit_changes(
+            repo,
+            &changes,
+            &format!(
+                "Undo {} operation{}",
+                steps,
+                if steps == 1 { "" } else { "s" }
+            ),
+        )?;
+    }
+
+    Ok(plan)
+}
+
+pub fn run(steps: usize, force: bool, dry_run: bool) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let plan = undo(&repo, steps, force, dry_run)?;
+
+    let verb = if dry_run { "Would undo" } else { "Undid" };
+    println!("{verb}:");
+    for commit in &plan.undone {
+        println!("  {} {}", &commit.commit[..7], commit.message);
+    }
+    for revert in &plan.issues {
+        if revert.removed {
+            println!("  {}: delete (created by undone operations)", revert.id);
+            continue;
+        }
+        if revert.changes.is_empty() {
+            println!("  {}: restore", revert.id);
+        }
+        for change in &revert.changes {
+            println!(
+                "  {}: {}: {:?} -> {:?}",
+                revert.id, change.field, change.from, change.to
+            );
+        }
+    }
+    for edge in &plan.edges_restored {
+        println!(
+            "  restore edge {} {} {}",
+            edge.source,
+            edge.edge_type.as_str(),
+            edge.target
+        );
+    }
+    for edge in &plan.edges_removed {
+        println!(
+            "  remove edge {} {} {}",
+            edge.source,
+            edge.edge_type.as_str(),
+            edge.target
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{close, dep, pull, push, update};
+    use crate::testing::{clones, create_issue, head, refs, repo};
+    use crate::types::EdgeType;
+
+    fn retitle(repo: &Repository, id: &str, title: &str) {
+        let changes = update::Changes {
+            title: Some(title.to_string()),
+            ..Default::default()
+        };
+        update::update(repo, id, changes).unwrap();
+    }
+
+    fn live_edges(repo: &Repository) -> Vec<String> {
+        let mut names: Vec<String> = snapshot::load_edges(repo)
+            .unwrap()
+            .iter()
+            .map(snapshot::edge_name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_undo_round_trips_issues_and_edges() {
+        let (_dir, repo) = repo();
+        let a = create_issue(&repo, "A");
+        let b = create_issue(&repo, "B");
+        dep::add_edge(&repo, &a.id, &b.id, EdgeType::DependsOn).unwrap();
+        let issues_before = snapshot::load_issues(&repo).unwrap();
+        let edges_before = live_edges(&repo);
+
+        retitle(&repo, &a.id, "A renamed");
+        close::close(&repo, &b.id, None).unwrap();
+        dep::remove_edge(&repo, &a.id, &b.id, EdgeType::DependsOn).unwrap();
+        let c = create_issue(&repo, "C");
+        dep::add_edge(&repo, &a.id, &c.id, EdgeType::RelatesTo).unwrap();
+        let last = head(&repo).id();
+
+        let plan = undo(&repo, 5, false, false).unwrap();
+        assert_eq!(plan.undone.len(), 5);
+        assert_eq!(plan.edges_restored.len(), 1);
+        assert_eq!(plan.edges_removed.len(), 1);
+
+        // Undo is a new commit on top, not a rewind
+        assert_eq!(head(&repo).parent_ids().collect::<Vec<_>>(), vec![last]);
+        let issues = snapshot::load_issues(&repo).unwrap();
+        assert_eq!(issues.len(), issues_before.len());
+        for (id, before) in &issues_before {
+            assert!(issues[id].changes_from(before).is_empty(), "{id} differs");
+        }
+        assert!(snapshot::load_all_issues(&repo).unwrap()[&c.id].deleted);
+        assert_eq!(live_edges(&repo), edges_before);
+    }
+
+    #[test]
+    fn test_dry_run_writes_nothing() {
+        let (_dir, repo) = repo();
+        let a = create_issue(&repo, "A");
+        retitle(&repo, &a.id, "A renamed");
+        let before = refs(&repo);
+
+        let plan = undo(&repo, 1, false, true).unwrap();
+        assert_eq!(plan.issues.len(), 1);
+        assert_eq!(plan.issues[0].changes[0].field, "title");
+        assert_eq!(refs(&repo), before);
+        assert_eq!(
+            snapshot::load_issue(&repo, &a.id).unwrap().title,
+            "A renamed"
+        );
+    }
+
+    #[test]
+    fn test_refuses_to_undo_pulled_commits() {
+        let (_dir, a, b, id) = clones();
+        retitle(&a, &id, "From a");
+        push::push(&a, "origin").unwrap();
+
+        // Fast-forwarded onto a's commit, which b never made
+        pull::pull(&b, "origin", false).unwrap();
+        assert!(matches!(
+            undo(&b, 1, false, false),
+            Err(Error::UndoAcrossPull(_))
+        ));
+
+        retitle(&b, &id, "From b");
+        undo(&b, 1, false, true).unwrap();
+        assert!(matches!(
+            undo(&b, 2, false, true),
+            Err(Error::UndoAcrossPull(_))
+        ));
+        undo(&b, 2, true, true).unwrap();
+
+        // A merge commit is a pull too
+        retitle(&a, &id, "From a again");
+        push::push(&a, "origin").unwrap();
+        pull::pull(&b, "origin", false).unwrap();
+        assert_eq!(head(&b).parent_count(), 2);
+        assert!(matches!(
+            undo(&b, 1, false, false),
+            Err(Error::UndoAcrossPull(_))
+        ));
+    }
+}
@@ -3,43 +3,69 @@ use git2::Repository;
 use crate::error::Error;
 use crate::snapshot;
 use crate::storage;
-use crate::types::{IssueType, Priority};
+use crate::types::{Issue, IssueType, Priority};
 
-pub fn run(
-    id_prefix: String,
-    title: Option<String>,
-    description: Option<String>,
-    priority: Option<String>,
-    issue_type: Option<String>,
-    labels: Option<Vec<String>>,
-) -> Result<(), Error> {
-    let repo = Repository::discover(".")?;
+/// Fields to change on an issue; `None` leaves the field as is
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub priority: Option<Priority>,
+    pub issue_type: Option<IssueType>,
+    pub labels: Option<Vec<String>>,
+}
 
-    let id = snapshot::find_issue_id(&repo, &id_prefix)?;
-    let mut issue = snapshot::load_issue(&repo, &id)?;
+/// Apply changes to an issue and return the updated issue
+pub fn update(repo: &Repository, id_prefix: &str, changes: Changes) -> Result<Issue, Error> {
+    let id = snapshot::find_issue_id(repo, id_prefix)?;
+    let before = snapshot::load_issue(repo, &id)?;
+    let mut issue = before.clone();
 
-    if let Some(t) = title {
+    if let Some(t) = changes.title {
         issue.title = t;
     }
-    if let Some(d) = description {
+    // Rewriting the description is how a merge conflict gets resolved
+    if let Some(d) = changes.description {
         issue.description = d;
+        issue.conflict = false;
     }
-    if let Some(p) = priority {
-        issue.priority = Priority::from_str(&p)?;
+    if let Some(p) = changes.priority {
+        issue.priority = p;
     }
-    if let Some(t) = issue_type {
-        issue.issue_type = IssueType::from_str(&t)?;
+    if let Some(t) = changes.issue_type {
+        issue.issue_type = t;
     }
-    if let Some(l) = labels {
+    if let Some(l) = changes.labels {
         issue.labels = l;
     }
 
-    issue.lamport += 1;
-    issue.updated_at = chrono::Utc::now().timestamp();
-    issue.editor = storage::get_editor()?;
+    issue.record_edit(&before, storage::get_editor(repo)?, snapshot::clock(repo)?);
+
+    snapshot::save_issue(repo, &issue, &format!("Update issue {id}"))?;
+
+    Ok(issue)
+}
+
+pub fn run(
+    id_prefix: String,
+    title: Option<String>,
+    description: Option<String>,
+    priority: Option<String>,
+    issue_type: Option<String>,
+    labels: Option<Vec<String>>,
+) -> Result<(), Error> {
+    let repo = Repository::discover(".")?;
+
+    let changes = Changes {
+        title,
+        description,
+        priority: priority.map(|p| p.parse()).transpose()?,
+        issue_type: issue_type.map(|t| t.parse()).transpose()?,
+        labels,
+    };
 
-    snapshot::save_issue(&repo, &issue, &format!("Update issue {id}"))?;
+    let issue = update(&repo, &id_prefix, changes)?;
 
-    println!("Updated {id}");
+    println!("Updated {}", issue.id);
     Ok(())
 }
@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
+use crate::snapshot::edge_name;
 use crate::types::{Edge, EdgeType};
 
 /// Check if adding a new edge would create a cycle in the dependency graph.
@@ -54,20 +55,110 @@ pub fn would_create_cycle(edges: &[Edge], source: &str, target: &str, edge_type:
     false
 }
 
+/// Find one cycle among the cycle-forming edges, returned as indices into
+/// `edges` in path order. Returns None if the graph is acyclic.
+pub fn find_cycle(edges: &[Edge]) -> Option<Vec<usize>> {
+    let mut adj: HashMap<&str, Vec<(usize, &str)>> = HashMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        if matches!(
+            edge.edge_type,
+            EdgeType::DependsOn | EdgeType::Blocks | EdgeType::ParentChild
+        ) {
+            adj.entry(edge.source.as_str())
+                .or_default()
+                .push((i, edge.target.as_str()));
+        }
+    }
+
+    let mut nodes: Vec<&str> = adj.keys().copied().collect();
+    nodes.sort();
+
+    // Iterative DFS; `path` holds the edges leading to the current node
+    let mut done: HashSet<&str> = HashSet::new();
+    for start in nodes {
+        if done.contains(start) {
+            continue;
+        }
+        let mut on_path: Vec<&str> = vec![start];
+        let mut path: Vec<usize> = Vec::new();
+        let mut cursor: Vec<usize> = vec![0];
+
+        while let Some(node) = on_path.last().copied() {
+            let next = cursor.last().copied().unwrap_or(0);
+            let neighbors = adj.get(node).map(Vec::as_slice).unwrap_or(&[]);
+            if next >= neighbors.len() {
+                done.insert(node);
+                on_path.pop();
+                cursor.pop();
+                path.pop();
+                continue;
+            }
+            *cursor.last_mut().unwrap() += 1;
+
+            let (edge_index, target) = neighbors[next];
+            if let Some(pos) = on_path.iter().position(|n| *n == target) {
+                let mut cycle = path[pos..].to_vec();
+                cycle.push(edge_index);
+                return Some(cycle);
+            }
+            if !done.contains(target) {
+                on_path.push(target);
+                cursor.push(0);
+                path.push(edge_index);
+            }
+        }
+    }
+    None
+}
+
+/// Live edges to set aside so the graph is valid, as indices into `edges`
+/// with the reason for each: edges whose issue is not among `issue_ids`, then
+/// the edge with the greatest name in each remaining cycle. The choice only
+/// depends on the edge set, so every clone merging the same state picks the
+/// same edges.
+pub fn invalid_edges(issue_ids: &HashSet<String>, edges: &[Edge]) -> Vec<(usize, String)> {
+    let mut order: Vec<usize> = (0..edges.len()).filter(|i| edges[*i].is_live()).collect();
+    order.sort_by_key(|i| edge_name(&edges[*i]));
+
+    let mut invalid = Vec::new();
+    let mut graph = Vec::new();
+    for i in order {
+        let edge = &edges[i];
+        let missing: Vec<&str> = [edge.source.as_str(), edge.target.as_str()]
+            .into_iter()
+            .filter(|id| !issue_ids.contains(*id))
+            .collect();
+        if missing.is_empty() {
+            graph.push(i);
+        } else {
+            invalid.push((
+                i,
+                format!("references missing or deleted issue {}", missing.join(", ")),
+            ));
+        }
+    }
+
+    loop {
+        let live: Vec<Edge> = graph.iter().map(|i| edges[*i].clone()).collect();
+        let Some(cycle) = find_cycle(&live) else {
+            break;
+        };
+        let drop = *cycle.iter().max_by_key(|i| edge_name(&live[**i])).unwrap();
+        let mut nodes: Vec<&str> = cycle.iter().map(|i| live[*i].source.as_str()).collect();
+        nodes.push(live[cycle[0]].source.as_str());
+        invalid.push((
+            graph.remove(drop),
+            format!("closes cycle {}", nodes.join(" -> ")),
+        ));
+    }
+    invalid
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{Edge, SCHEMA_VERSION};
-
-    fn make_edge(source: &str, target: &str, edge_type: EdgeType) -> Edge {
-        Edge {
-            schema_version: SCHEMA_VERSION,
-            source: source.to_string(),
-            target: target.to_string(),
-            edge_type,
-            created_at: 0,
-        }
-    }
+    use crate::testing::make_edge;
+    use crate::types::Edge;
 
     #[test]
     fn test_no_cycle_empty() {
@@ -98,4 +189,60 @@ mod tests {
         // RelatesTo edges don't form cycles
         assert!(!would_create_cycle(&edges, "b", "a", EdgeType::RelatesTo));
     }
+
+    #[test]
+    fn test_find_cycle_acyclic() {
+        let edges = vec![
+            make_edge("a", "b", EdgeType::DependsOn),
+            make_edge("b", "c", EdgeType::DependsOn),
+            make_edge("c", "a", EdgeType::RelatesTo),
+        ];
+        assert_eq!(find_cycle(&edges), None);
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let edges = vec![
+            make_edge("x", "a", EdgeType::DependsOn),
+            make_edge("a", "b", EdgeType::DependsOn),
+            make_edge("b", "c", EdgeType::Blocks),
+            make_edge("c", "a", EdgeType::ParentChild),
+        ];
+        assert_eq!(find_cycle(&edges), Some(vec![1, 2, 3]));
+    }
+
+    fn ids(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_invalid_edges_breaks_cycle_at_greatest_name() {
+        let edges = vec![
+            make_edge("b", "a", EdgeType::DependsOn),
+            make_edge("a", "b", EdgeType::DependsOn),
+        ];
+        let invalid = invalid_edges(&ids(&["a", "b"]), &edges);
+        assert_eq!(invalid, vec![(0, "closes cycle a -> b -> a".to_string())]);
+
+        // The same edges in another order give the same choice
+        let reversed: Vec<Edge> = edges.iter().rev().cloned().collect();
+        let invalid = invalid_edges(&ids(&["a", "b"]), &reversed);
+        assert_eq!(invalid[0].0, 1);
+    }
+
+    #[test]
+    fn test_invalid_edges_dangling_and_quarantined() {
+        let mut quarantined = make_edge("b", "a", EdgeType::DependsOn);
+        quarantined.quarantine = Some("closes cycle a -> b -> a".to_string());
+        let edges = vec![
+            make_edge("a", "b", EdgeType::DependsOn),
+            quarantined,
+            make_edge("a", "gone", EdgeType::RelatesTo),
+        ];
+        let invalid = invalid_edges(&ids(&["a", "b"]), &edges);
+        assert_eq!(
+            invalid,
+            vec![(2, "references missing or deleted issue gone".to_string())]
+        );
+    }
 }
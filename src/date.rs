@@ -0,0 +1,51 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+use crate::error::Error;
+
+/// Parse a user-supplied date into a Unix timestamp (UTC).
+/// Accepts RFC 3339, `YYYY-MM-DD HH:MM[:SS]`, `YYYY-MM-DD` and raw timestamps,
+/// optionally prefixed with `@` as in git.
+pub fn parse_date(s: &str) -> Result<i64, Error> {
+    let s = s.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.timestamp());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(s, format) {
+            return Ok(dt.and_utc().timestamp());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp());
+    }
+    if let Ok(ts) = s.strip_prefix('@').unwrap_or(s).parse::<i64>() {
+        return Ok(ts);
+    }
+    Err(Error::InvalidDate(s.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_day() {
+        assert_eq!(parse_date("2024-01-09").unwrap(), 1704758400);
+    }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        assert_eq!(parse_date("2024-01-09T06:40:00+02:00").unwrap(), 1704775200);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_date("1704758400").unwrap(), 1704758400);
+        assert_eq!(parse_date("@1704758400").unwrap(), 1704758400);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse_date("last tuesday").is_err());
+    }
+}
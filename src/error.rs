@@ -5,7 +5,19 @@ pub enum Error {
     Git(git2::Error),
     Io(std::io::Error),
     Json(serde_json::Error),
-    SchemaMismatch { expected: u32, found: u32 },
+    SchemaMismatch {
+        expected: u32,
+        found: u32,
+    },
+    SchemaTooNew {
+        supported: u32,
+        found: u32,
+    },
+    RemoteSchemaTooNew {
+        remote: String,
+        supported: u32,
+        found: u32,
+    },
     NoIdentity(String),
     NotFound(String),
     AmbiguousId(String, Vec<String>),
@@ -18,12 +30,35 @@ pub enum Error {
     AlreadyClosed(String),
     NotClosed(String),
     InvalidPriority(String),
+    InvalidStatus(String),
     InvalidIssueType(String),
+    InvalidResolution(String),
+    NoConflict(String),
     NoEdgeTarget,
     SelfReference(String),
     DuplicateEdge(String, String),
     WouldCreateCycle(String, String),
     InvalidSnapshot,
+    InvalidDate(String),
+    InvalidExport(String),
+    NoSnapshotAt(String),
+    RemoteNotFetched(String),
+    RemoteNotInitialized(String),
+    NotQuarantined(String),
+    AtNotSupported,
+    NothingToUndo(usize),
+    UndoAcrossPull(String),
+    FsckFailed(usize),
+    LostClaims(usize),
+    PushRejected {
+        remote: String,
+        reason: String,
+    },
+    PushGaveUp {
+        remote: String,
+        attempts: usize,
+        reason: String,
+    },
     LockFailed(String),
 }
 
@@ -36,6 +71,18 @@ impl fmt::Display for Error {
             Error::SchemaMismatch { expected, found } => {
                 write!(f, "Schema mismatch: expected {expected}, found {found}")
             }
+            Error::SchemaTooNew { supported, found } => write!(
+                f,
+                "Schema version {found} is newer than this st supports ({supported}); upgrade sterna"
+            ),
+            Error::RemoteSchemaTooNew {
+                remote,
+                supported,
+                found,
+            } => write!(
+                f,
+                "Remote '{remote}' uses schema version {found}, but this st supports up to {supported}; upgrade sterna before pulling"
+            ),
             Error::NoIdentity(msg) => write!(f, "No identity: {msg}"),
             Error::NotFound(id) => write!(f, "Issue not found: {id}"),
             Error::AmbiguousId(prefix, matches) => {
@@ -50,7 +97,15 @@ impl fmt::Display for Error {
             Error::AlreadyClosed(id) => write!(f, "Issue {id} is already closed"),
             Error::NotClosed(id) => write!(f, "Issue {id} is not closed"),
             Error::InvalidPriority(p) => write!(f, "Invalid priority: {p}"),
+            Error::InvalidStatus(s) => write!(f, "Invalid status: {s}"),
             Error::InvalidIssueType(t) => write!(f, "Invalid issue type: {t}"),
+            Error::InvalidResolution(r) => {
+                write!(
+                    f,
+                    "Invalid resolution: {r} (expected local, remote or merged)"
+                )
+            }
+            Error::NoConflict(name) => write!(f, "No open conflict matches: {name}"),
             Error::NoEdgeTarget => write!(
                 f,
                 "Must specify one of: --needs, --blocks, --relates-to, --parent, --duplicates"
@@ -59,6 +114,49 @@ impl fmt::Display for Error {
             Error::DuplicateEdge(s, t) => write!(f, "Edge already exists: {s} -> {t}"),
             Error::WouldCreateCycle(s, t) => write!(f, "Would create cycle: {s} -> {t}"),
             Error::InvalidSnapshot => write!(f, "Invalid snapshot format"),
+            Error::InvalidDate(d) => write!(f, "Invalid date: {d}"),
+            Error::InvalidExport(msg) => write!(f, "Invalid export file: {msg}"),
+            Error::NoSnapshotAt(at) => write!(f, "No snapshot at or before {at}"),
+            Error::RemoteNotInitialized(remote) => {
+                write!(
+                    f,
+                    "Remote '{remote}' has no Sterna snapshot (refs/sterna/snapshot). \
+                     Create one with 'st init' and 'st push {remote}'."
+                )
+            }
+            Error::RemoteNotFetched(remote) => {
+                write!(
+                    f,
+                    "No snapshot fetched from '{remote}'. Run 'st fetch {remote}' first."
+                )
+            }
+            Error::NotQuarantined(name) => write!(f, "No quarantined edge matches: {name}"),
+            Error::AtNotSupported => {
+                write!(f, "--at is only supported by list, get, ready and export")
+            }
+            Error::NothingToUndo(steps) => {
+                write!(f, "Cannot undo {steps} operations: not enough history")
+            }
+            Error::UndoAcrossPull(commit) => write!(
+                f,
+                "Refusing to undo {}, which came in with a pull; use --force to undo it anyway",
+                &commit[..7]
+            ),
+            Error::FsckFailed(count) => write!(f, "Snapshot has {count} unrepaired problems"),
+            Error::LostClaims(count) => {
+                write!(f, "{count} of your claims were overwritten by the remote")
+            }
+            Error::PushRejected { remote, reason } => {
+                write!(f, "Push to '{remote}' was rejected: {reason}")
+            }
+            Error::PushGaveUp {
+                remote,
+                attempts,
+                reason,
+            } => write!(
+                f,
+                "Push to '{remote}' gave up after {attempts} rejected attempts: {reason}"
+            ),
             Error::LockFailed(msg) => write!(f, "Failed to acquire lock: {msg}"),
         }
     }
@@ -0,0 +1,22 @@
+//! Sterna - Git-native issue tracker.
+//!
+//! All state lives in `refs/sterna/snapshot`. The `commands` module exposes
+//! each CLI operation as a plain function that takes a `Repository` and
+//! returns values; the `run` functions alongside them are what the `st`
+//! binary calls to print results.
+
+pub mod commands;
+pub mod dag;
+pub mod date;
+pub mod error;
+pub mod id;
+pub mod merge;
+pub mod migration;
+pub mod snapshot;
+pub mod storage;
+#[cfg(test)]
+mod testing;
+pub mod types;
+
+pub use error::Error;
+pub use types::{Edge, EdgeType, Issue, IssueType, Priority, Status};
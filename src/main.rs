@@ -1,17 +1,16 @@
-use clap::{Parser, Subcommand};
-
-mod commands;
-mod dag;
-mod error;
-mod id;
-mod snapshot;
-mod storage;
-mod types;
+use clap::{ArgGroup, Parser, Subcommand};
+use sterna::Error;
+use sterna::commands;
 
 #[derive(Parser)]
 #[command(name = "st", version = env!("VERGEN_GIT_DESCRIBE"))]
 #[command(about = "Sterna - Git-native issue tracker")]
 struct Cli {
+    /// Read from the snapshot at this date (@<unix time> for timestamps) or commit
+    /// instead of the current one
+    #[arg(long, global = true)]
+    at: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -19,7 +18,11 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize Sterna in the current repository
-    Init,
+    Init {
+        /// Adopt this remote's snapshot (name, URL or path) instead of starting empty
+        #[arg(long)]
+        from: Option<String>,
+    },
 
     /// Create a new issue
     Create {
@@ -68,6 +71,52 @@ enum Commands {
         json: bool,
     },
 
+    /// Show the change timeline of an issue
+    History {
+        /// Issue ID or prefix
+        id: String,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compare two tracker states: snapshot commits, dates, fetched remotes or export files
+    Diff {
+        /// Old side (default: the snapshot before the current one)
+        from: Option<String>,
+
+        /// New side (default: the current snapshot)
+        to: Option<String>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show the activity feed of all snapshot commits
+    Log {
+        /// Only events at or after this date (YYYY-MM-DD, RFC 3339 or Unix time)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only events by this editor (email)
+        #[arg(long)]
+        editor: Option<String>,
+
+        /// Only events touching this issue (ID or prefix)
+        #[arg(long)]
+        issue: Option<String>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Output as JSON Lines (one event per line)
+        #[arg(long, conflicts_with = "json")]
+        jsonl: bool,
+    },
+
     /// Claim an issue to work on
     Claim {
         /// Issue ID or prefix
@@ -98,6 +147,12 @@ enum Commands {
         reason: Option<String>,
     },
 
+    /// Delete an issue and the edges that reference it
+    Delete {
+        /// Issue ID or prefix
+        id: String,
+    },
+
     /// Reopen a closed issue
     Reopen {
         /// Issue ID or prefix
@@ -136,15 +191,58 @@ enum Commands {
         #[arg(short = 't', long = "type")]
         issue_type: Option<String>,
 
-        /// New labels (replaces existing)
+        /// New labels (replaces existing; see `st label` for single edits)
         #[arg(short, long)]
         label: Option<Vec<String>>,
     },
 
+    /// Revert the last snapshot operations with a new commit
+    Undo {
+        /// Number of operations to undo
+        #[arg(short = 'n', long, default_value_t = 1)]
+        steps: usize,
+
+        /// Show what would be reverted without committing
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Allow undoing across a pull merge
+        #[arg(long)]
+        force: bool,
+    },
+
     /// Manage dependencies between issues
     #[command(subcommand)]
     Dep(DepCommands),
 
+    /// Add or remove a single label
+    #[command(subcommand)]
+    Label(LabelCommands),
+
+    /// Configure git remotes to carry the snapshot
+    #[command(subcommand)]
+    Remote(RemoteCommands),
+
+    /// List edges a pull set aside, or resolve one
+    Quarantine {
+        #[command(subcommand)]
+        command: Option<QuarantineCommands>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List versions a pull or import discarded, or resolve one
+    Conflicts {
+        #[command(subcommand)]
+        command: Option<ConflictsCommands>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Export all issues and edges to JSON
     Export {
         /// Output file (default: stdout)
@@ -156,8 +254,30 @@ enum Commands {
     Import {
         /// Input file
         file: String,
+
+        /// Show what would be imported without writing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
     },
 
+    /// Check the snapshot tree for integrity problems
+    Fsck {
+        /// Fix repairable problems in a single commit
+        #[arg(long)]
+        repair: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Rewrite all issues and edges at the current schema version
+    Migrate,
+
     /// Remove all Sterna data from this repository
     Purge {
         /// Skip confirmation prompt
@@ -167,20 +287,68 @@ enum Commands {
 
     /// Push snapshot to remote
     Push {
-        /// Remote name (default: origin)
+        /// Remote name, URL or path (default: origin)
+        remote: Option<String>,
+
+        /// Times to pull and retry when the remote rejects the push
+        #[arg(long, default_value_t = commands::push::DEFAULT_RETRIES)]
+        retries: usize,
+
+        /// Exit non-zero if a retry merge overwrote any of your claims
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// Fetch a remote snapshot into its tracking ref without merging
+    Fetch {
+        /// Remote name, URL or path (default: origin)
+        remote: Option<String>,
+    },
+
+    /// Show unpushed and unmerged snapshot changes and your claims
+    Status {
+        /// Remote to compare with (default: origin)
         remote: Option<String>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
     },
 
     /// Pull and merge snapshot from remote
     Pull {
-        /// Remote name (default: origin)
+        /// Remote name, URL or path (default: origin)
         remote: Option<String>,
+
+        /// Exit non-zero if any of your claims were overwritten
+        #[arg(long)]
+        strict: bool,
+
+        /// Fetch and show what the merge would write without writing it
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
     },
 
     /// Pull then push (convenience command)
     Sync {
-        /// Remote name (default: origin)
+        /// Remote name, URL or path (default: origin)
         remote: Option<String>,
+
+        /// Times to pull and retry when the remote rejects the push
+        #[arg(long, default_value_t = commands::push::DEFAULT_RETRIES)]
+        retries: usize,
+
+        /// Sync with every configured remote: pull from all, then push to all
+        #[arg(long, conflicts_with = "remote")]
+        all: bool,
+
+        /// Exit non-zero if any of your claims were overwritten; the sync still completes
+        #[arg(long)]
+        strict: bool,
     },
 
     /// Show onboarding info for agents
@@ -198,6 +366,71 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum QuarantineCommands {
+    /// Restore a quarantined edge or drop it for good
+    #[command(group(ArgGroup::new("action").required(true).args(["restore", "drop"])))]
+    Resolve {
+        /// Edge name (source_target_type) or unique prefix
+        edge: String,
+
+        /// Make the edge live again (fails if it still closes a cycle)
+        #[arg(long)]
+        restore: bool,
+
+        /// Remove the edge
+        #[arg(long)]
+        drop: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConflictsCommands {
+    /// Settle a conflict by keeping one version
+    Resolve {
+        /// Conflict name (id_lamport), or issue ID if it has one open conflict
+        id: String,
+
+        /// Version to keep: local, remote, or merged (the issue as it is)
+        #[arg(long)]
+        take: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum RemoteCommands {
+    /// Add snapshot fetch and push refspecs so plain git fetch/push carry issues
+    Setup {
+        /// Remote name (default: origin)
+        remote: Option<String>,
+
+        /// Remove the refspecs again
+        #[arg(long)]
+        remove: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum LabelCommands {
+    /// Add a label to an issue
+    Add {
+        /// Issue ID
+        id: String,
+
+        /// Label to add
+        label: String,
+    },
+
+    /// Remove a label from an issue
+    Remove {
+        /// Issue ID
+        id: String,
+
+        /// Label to remove
+        label: String,
+    },
+}
+
 #[derive(Subcommand)]
 enum DepCommands {
     /// Add a dependency between issues
@@ -255,9 +488,23 @@ enum DepCommands {
 
 fn main() {
     let cli = Cli::parse();
+    let at = cli.at;
+
+    if at.is_some()
+        && !matches!(
+            cli.command,
+            Commands::List { .. }
+                | Commands::Get { .. }
+                | Commands::Ready { .. }
+                | Commands::Export { .. }
+        )
+    {
+        eprintln!("error: {}", Error::AtNotSupported);
+        std::process::exit(1);
+    }
 
     let result = match cli.command {
-        Commands::Init => commands::init::run(),
+        Commands::Init { from } => commands::init::run(from),
         Commands::Create {
             title,
             description,
@@ -269,13 +516,23 @@ fn main() {
             status,
             issue_type,
             json,
-        } => commands::list::run(status, issue_type, json),
-        Commands::Get { id, json } => commands::get::run(id, json),
+        } => commands::list::run(at, status, issue_type, json),
+        Commands::Get { id, json } => commands::get::run(at, id, json),
+        Commands::History { id, json } => commands::history::run(id, json),
+        Commands::Diff { from, to, json } => commands::diff::run(from, to, json),
+        Commands::Log {
+            since,
+            editor,
+            issue,
+            json,
+            jsonl,
+        } => commands::log::run(since, editor, issue, json, jsonl),
         Commands::Claim { id, context } => commands::claim::run(id, context),
         Commands::Release { id, reason } => commands::release::run(id, reason),
         Commands::Close { id, reason } => commands::close::run(id, reason),
+        Commands::Delete { id } => commands::delete::run(id),
         Commands::Reopen { id, reason } => commands::reopen::run(id, reason),
-        Commands::Ready { json } => commands::ready::run(json),
+        Commands::Ready { json } => commands::ready::run(at, json),
         Commands::Update {
             id,
             title,
@@ -284,6 +541,11 @@ fn main() {
             issue_type,
             label,
         } => commands::update::run(id, title, description, priority, issue_type, label),
+        Commands::Undo {
+            steps,
+            dry_run,
+            force,
+        } => commands::undo::run(steps, force, dry_run),
         Commands::Dep(cmd) => match cmd {
             DepCommands::Add {
                 source,
@@ -302,12 +564,53 @@ fn main() {
                 duplicates,
             } => commands::dep::remove(source, needs, blocks, relates_to, parent, duplicates),
         },
-        Commands::Export { output } => commands::export::run(output),
-        Commands::Import { file } => commands::import::run(file),
+        Commands::Label(cmd) => match cmd {
+            LabelCommands::Add { id, label } => commands::label::add(id, label),
+            LabelCommands::Remove { id, label } => commands::label::remove(id, label),
+        },
+        Commands::Quarantine { command, json } => match command {
+            None => commands::quarantine::run(json),
+            Some(QuarantineCommands::Resolve { edge, restore, .. }) => {
+                commands::quarantine::run_resolve(edge, restore)
+            }
+        },
+        Commands::Conflicts { command, json } => match command {
+            None => commands::conflicts::run(json),
+            Some(ConflictsCommands::Resolve { id, take }) => {
+                commands::conflicts::run_resolve(id, take)
+            }
+        },
+        Commands::Remote(cmd) => match cmd {
+            RemoteCommands::Setup { remote, remove } => commands::remote::run_setup(remote, remove),
+        },
+        Commands::Export { output } => commands::export::run(at, output),
+        Commands::Import {
+            file,
+            dry_run,
+            json,
+        } => commands::import::run(file, dry_run, json),
+        Commands::Fsck { repair, json } => commands::fsck::run(repair, json),
+        Commands::Migrate => commands::migrate::run(),
         Commands::Purge { yes } => commands::purge::run(yes),
-        Commands::Push { remote } => commands::push::run(remote),
-        Commands::Pull { remote } => commands::pull::run(remote),
-        Commands::Sync { remote } => commands::sync::run(remote),
+        Commands::Push {
+            remote,
+            retries,
+            strict,
+        } => commands::push::run(remote, retries, strict),
+        Commands::Fetch { remote } => commands::fetch::run(remote),
+        Commands::Status { remote, json } => commands::status::run(remote, json),
+        Commands::Pull {
+            remote,
+            strict,
+            dry_run,
+            json,
+        } => commands::pull::run(remote, strict, dry_run, json),
+        Commands::Sync {
+            remote,
+            retries,
+            all,
+            strict,
+        } => commands::sync::run(remote, retries, all, strict),
         Commands::Onboard { export } => commands::onboard::run(export),
         Commands::Prime { export } => commands::prime::run(export),
     };
@@ -0,0 +1,621 @@
+//! Merging two versions of the same issue or edge from different clones.
+//!
+//! Pull and import both go through these functions so that every path that
+//! brings in remote state converges the same way.
+
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::types::{Conflict, Edge, Issue};
+
+/// Total order over conflicting versions of a value: Lamport clock, then
+/// edit timestamp, then editor, then a hash of the value itself. Two keys are
+/// only equal when the values are identical, so both sides of a merge always
+/// pick the same winner.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct VersionKey<'a> {
+    clock: u64,
+    timestamp: i64,
+    editor: &'a str,
+    hash: [u8; 20],
+}
+
+impl<'a> VersionKey<'a> {
+    fn new<T: Serialize>(issue: &'a Issue, clock: u64, value: &T) -> Self {
+        VersionKey {
+            clock,
+            timestamp: issue.updated_at,
+            editor: &issue.editor,
+            hash: content_hash(value),
+        }
+    }
+}
+
+fn content_hash<T: Serialize>(value: &T) -> [u8; 20] {
+    let bytes = serde_json::to_vec(value).unwrap_or_default();
+    Sha1::digest(&bytes).into()
+}
+
+/// Whether the remote version of a field group beats the local one
+fn remote_wins<T: Serialize>(
+    local: &Issue,
+    local_clock: u64,
+    local_value: &T,
+    remote: &Issue,
+    remote_clock: u64,
+    remote_value: &T,
+) -> bool {
+    VersionKey::new(remote, remote_clock, remote_value)
+        > VersionKey::new(local, local_clock, local_value)
+}
+
+/// Merge two versions of an issue field by field. Each field group keeps the
+/// value from the side that wrote it last, so concurrent edits to different
+/// fields both survive. Labels are an observed-remove set and merge by union.
+/// Ties are broken by a total order (see `VersionKey`) and deletion wins over
+/// presence, so `merge_issue(a, b) == merge_issue(b, a)`.
+pub fn merge_issue(local: &Issue, remote: &Issue) -> Issue {
+    let mut merged = local.clone();
+    let (lc, rc) = (&local.clocks, &remote.clocks);
+
+    if remote_wins(
+        local,
+        lc.title,
+        &local.title,
+        remote,
+        rc.title,
+        &remote.title,
+    ) {
+        merged.title = remote.title.clone();
+        merged.clocks.title = rc.title;
+    }
+    if remote_description_wins(local, remote) {
+        merged.description = remote.description.clone();
+        merged.conflict = remote.conflict;
+        merged.clocks.description = rc.description;
+    }
+    if remote_status_wins(local, remote) {
+        merged.status = remote.status;
+        merged.claimed = remote.claimed;
+        merged.claimed_by = remote.claimed_by.clone();
+        merged.claim_context = remote.claim_context.clone();
+        merged.claimed_at = remote.claimed_at;
+        merged.reason = remote.reason.clone();
+        merged.clocks.status = rc.status;
+    }
+    if remote_wins(
+        local,
+        lc.priority,
+        &local.priority,
+        remote,
+        rc.priority,
+        &remote.priority,
+    ) {
+        merged.priority = remote.priority;
+        merged.clocks.priority = rc.priority;
+    }
+    if remote_wins(
+        local,
+        lc.issue_type,
+        &local.issue_type,
+        remote,
+        rc.issue_type,
+        &remote.issue_type,
+    ) {
+        merged.issue_type = remote.issue_type;
+        merged.clocks.issue_type = rc.issue_type;
+    }
+    merged.label_tags.merge(&remote.label_tags);
+    merged.labels = merged.label_tags.labels();
+    if (rc.deleted, remote.deleted) > (lc.deleted, local.deleted) {
+        merged.deleted = remote.deleted;
+        merged.clocks.deleted = rc.deleted;
+    }
+
+    // The issue-level metadata follows the greatest version overall
+    if remote_wins(local, local.lamport, local, remote, remote.lamport, remote) {
+        merged.lamport = remote.lamport;
+        merged.updated_at = remote.updated_at;
+        merged.editor = remote.editor.clone();
+    }
+    merged.created_at = local.created_at.min(remote.created_at);
+    merged
+}
+
+/// The description group: the text and its `conflict` flag
+fn description_group(issue: &Issue) -> impl Serialize + use<> {
+    (issue.description.clone(), issue.conflict)
+}
+
+/// Whether the merge takes the description group from `remote`
+fn remote_description_wins(local: &Issue, remote: &Issue) -> bool {
+    remote_wins(
+        local,
+        local.clocks.description,
+        &description_group(local),
+        remote,
+        remote.clocks.description,
+        &description_group(remote),
+    )
+}
+
+/// The status group: status plus the claim fields and reason
+fn status_group(issue: &Issue) -> impl Serialize + use<> {
+    (
+        issue.status,
+        issue.claimed,
+        issue.claimed_by.clone(),
+        issue.claim_context.clone(),
+        issue.claimed_at,
+        issue.reason.clone(),
+    )
+}
+
+/// Whether the merge takes the status group (and so any claim) from `remote`
+fn remote_status_wins(local: &Issue, remote: &Issue) -> bool {
+    remote_wins(
+        local,
+        local.clocks.status,
+        &status_group(local),
+        remote,
+        remote.clocks.status,
+        &status_group(remote),
+    )
+}
+
+/// Merge two versions of an issue that share `base` as their last common
+/// version. Like `merge_issue`, except that when both sides changed the
+/// description it is merged line by line against the base instead of one side
+/// winning; hunks both sides changed differently are kept between conflict
+/// markers and the issue is flagged with `conflict`.
+pub fn merge_issue_with_base(local: &Issue, remote: &Issue, base: Option<&Issue>) -> Issue {
+    let mut merged = merge_issue(local, remote);
+    let Some(base) = base else {
+        return merged;
+    };
+    // Same text: `merge_issue` already picked the flag by version order
+    if local.description == remote.description {
+        return merged;
+    }
+
+    // Order the sides the same way on every clone so the result converges
+    let (first, second) = if remote_description_wins(local, remote) {
+        (local, remote)
+    } else {
+        (remote, local)
+    };
+    let (text, conflicted) = merge_text(
+        &base.description,
+        (&first.description, &first.editor),
+        (&second.description, &second.editor),
+    );
+
+    // A result equal to one side is that side's version, clock and all
+    if let Some(side) = [local, remote].into_iter().find(|i| i.description == text) {
+        merged.description = side.description.clone();
+        merged.conflict = side.conflict;
+        merged.clocks.description = side.clocks.description;
+        return merged;
+    }
+    let clock = local.clocks.description.max(remote.clocks.description) + 1;
+    merged.conflict = conflicted || ((local.conflict || remote.conflict) && has_markers(&text));
+    merged.description = text;
+    merged.clocks.description = clock;
+    merged.lamport = merged.lamport.max(clock);
+    merged
+}
+
+/// Three-way line merge of two texts derived from `base`; each side comes
+/// with a label for its conflict marker. Lines both sides inserted at the
+/// same place are all kept, `first` then `second`. Returns the merged text
+/// and whether any hunk conflicted.
+pub fn merge_text(base: &str, first: (&str, &str), second: (&str, &str)) -> (String, bool) {
+    let o: Vec<&str> = base.split_inclusive('\n').collect();
+    let a: Vec<&str> = first.0.split_inclusive('\n').collect();
+    let b: Vec<&str> = second.0.split_inclusive('\n').collect();
+    let (ma, mb) = (line_matches(&o, &a), line_matches(&o, &b));
+
+    let mut out = String::new();
+    let mut conflicted = false;
+    let (mut io, mut ia, mut ib) = (0, 0, 0);
+    loop {
+        // The next base line both sides kept ends the current hunk
+        let stable = (io..o.len()).find_map(|i| Some((i, ma[i]?, mb[i]?)));
+        let (jo, ja, jb) = stable.unwrap_or((o.len(), a.len(), b.len()));
+        let (ho, ha, hb) = (&o[io..jo], &a[ia..ja], &b[ib..jb]);
+
+        if ha == ho {
+            push_lines(&mut out, hb);
+        } else if hb == ho || ha == hb {
+            push_lines(&mut out, ha);
+        } else if ho.is_empty() {
+            push_lines(&mut out, ha);
+            push_lines(&mut out, hb);
+        } else {
+            conflicted = true;
+            push_lines(&mut out, &[&format!("<<<<<<< {}\n", first.1)]);
+            push_lines(&mut out, ha);
+            push_lines(&mut out, &["=======\n"]);
+            push_lines(&mut out, hb);
+            push_lines(&mut out, &[&format!(">>>>>>> {}\n", second.1)]);
+        }
+
+        let Some((jo, ja, jb)) = stable else {
+            break;
+        };
+        out.push_str(o[jo]);
+        (io, ia, ib) = (jo + 1, ja + 1, jb + 1);
+    }
+    (out, conflicted)
+}
+
+/// For each line of `base`, the line of `other` it is matched with in a
+/// longest common subsequence
+fn line_matches(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let (n, m) = (base.len(), other.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if base[i] == other[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut matches = vec![None; n];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if base[i] == other[j] {
+            matches[i] = Some(j);
+            (i, j) = (i + 1, j + 1);
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+/// Append lines, starting a new line first if the text so far lacks one
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(line);
+    }
+}
+
+/// Whether a text still contains a conflict marker line
+pub fn has_markers(text: &str) -> bool {
+    text.lines()
+        .any(|l| l.starts_with("<<<<<<< ") || l == "=======" || l.starts_with(">>>>>>> "))
+}
+
+/// Merge two versions of an edge. Higher Lamport wins; on a tie, removal wins
+/// over presence, then the greater editor and content hash.
+pub fn merge_edge(local: &Edge, remote: &Edge) -> Edge {
+    let key = |e: &Edge| (e.lamport, e.deleted, e.editor.clone(), content_hash(e));
+    if key(remote) > key(local) {
+        remote.clone()
+    } else {
+        local.clone()
+    }
+}
+
+/// Field groups both sides changed (relative to `base`, or at all without
+/// one) where `merged` kept only one side's value. Labels merge by union and
+/// a description merged line by line keeps both sides, so neither counts.
+pub fn discarded_fields(
+    local: &Issue,
+    remote: &Issue,
+    merged: &Issue,
+    base: Option<&Issue>,
+) -> Vec<String> {
+    type Value = fn(&Issue) -> serde_json::Value;
+    let groups: [(&str, Value); 5] = [
+        ("title", |i| serde_json::json!(i.title)),
+        ("description", |i| serde_json::json!(description_group(i))),
+        ("status", |i| serde_json::json!(status_group(i))),
+        ("priority", |i| serde_json::json!(i.priority)),
+        ("type", |i| serde_json::json!(i.issue_type)),
+    ];
+    groups
+        .into_iter()
+        .filter(|(_, value)| {
+            let (l, r, m) = (value(local), value(remote), value(merged));
+            let concurrent = l != r && base.is_none_or(|b| value(b) != l && value(b) != r);
+            concurrent && (m == l) != (m == r)
+        })
+        .map(|(field, _)| field.to_string())
+        .collect()
+}
+
+/// Merge two copies of the same conflict record (same entry name). A record
+/// is only ever written again to resolve it, so a resolution wins over an
+/// open record; two resolutions are ordered by editor, then content.
+pub fn merge_conflict(local: &Conflict, remote: &Conflict) -> Conflict {
+    let key = |c: &Conflict| (c.resolution.is_some(), c.editor.clone(), content_hash(c));
+    if key(remote) > key(local) {
+        remote.clone()
+    } else {
+        local.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{edit, make_edge, make_issue};
+    use crate::types::{EdgeType, IssueType, Priority, Resolution, SCHEMA_VERSION, Status};
+
+    #[test]
+    fn test_concurrent_edits_to_different_fields_both_survive() {
+        let base = make_issue("st-a");
+        let local = edit(&base, "a@x", |i| i.priority = Priority::High);
+        let remote = edit(&base, "b@x", |i| i.title = "Renamed".to_string());
+
+        for merged in [merge_issue(&local, &remote), merge_issue(&remote, &local)] {
+            assert_eq!(merged.title, "Renamed");
+            assert_eq!(merged.priority, Priority::High);
+            assert_eq!(merged.lamport, 2);
+        }
+    }
+
+    #[test]
+    fn test_later_clock_wins_same_field() {
+        let base = make_issue("st-a");
+        let local = edit(&base, "a@x", |i| i.title = "Local".to_string());
+        let remote = edit(&base, "b@x", |i| i.title = "Remote".to_string());
+        let remote = edit(&remote, "b@x", |i| i.title = "Remote again".to_string());
+
+        assert_eq!(merge_issue(&local, &remote).title, "Remote again");
+        assert_eq!(merge_issue(&remote, &local).title, "Remote again");
+    }
+
+    #[test]
+    fn test_edit_does_not_resurrect_deleted_issue() {
+        let base = make_issue("st-a");
+        let deleted = edit(&base, "a@x", |i| i.deleted = true);
+        let edited = edit(&base, "b@x", |i| i.title = "Edited".to_string());
+        let edited = edit(&edited, "b@x", |i| i.priority = Priority::Low);
+
+        let merged = merge_issue(&edited, &deleted);
+        assert!(merged.deleted);
+        assert_eq!(merged.title, "Edited");
+    }
+
+    #[test]
+    fn test_conflict_flag_travels_with_same_text() {
+        let base = edit(&make_issue("st-a"), "a@x", |i| {
+            i.description = "<<<<<<< a@x\nx\n=======\ny\n>>>>>>> b@x".to_string();
+            i.conflict = true;
+        });
+        // Accepting the markers as-is clears the flag but keeps the text
+        let resolved = edit(&base, "b@x", |i| i.conflict = false);
+
+        for merged in [
+            merge_issue(&base, &resolved),
+            merge_issue(&resolved, &base),
+            merge_issue_with_base(&base, &resolved, Some(&base)),
+            merge_issue_with_base(&resolved, &base, Some(&base)),
+        ] {
+            assert!(!merged.conflict);
+            assert_eq!(merged.clocks.description, resolved.clocks.description);
+        }
+    }
+
+    #[test]
+    fn test_conflicting_same_clock_edits_converge() {
+        let base = edit(&make_issue("st-a"), "a@x", |i| {
+            i.description = "<<<<<<< a@x\nx\n=======\ny\n>>>>>>> b@x".to_string();
+            i.conflict = true;
+        });
+        let mut local = edit(&base, "a@x", |i| {
+            i.title = "From a".to_string();
+            i.labels = vec!["a".to_string()];
+        });
+        let mut remote = edit(&base, "b@x", |i| {
+            i.title = "From b".to_string();
+            i.conflict = false;
+            i.labels = vec!["b".to_string()];
+        });
+        local.updated_at = 200;
+        remote.updated_at = 200;
+
+        let ab = merge_issue(&local, &remote);
+        let ba = merge_issue(&remote, &local);
+        assert_eq!(ab, ba);
+        assert_eq!(ab.title, "From b");
+        assert_eq!(ab.editor, "b@x");
+        assert!(!ab.conflict && !ba.conflict);
+        assert_eq!(ab.labels, vec!["a", "b"]);
+        assert_eq!(ab.label_tags, ba.label_tags);
+        assert_eq!(ab.clocks, ba.clocks);
+        assert_eq!(
+            serde_json::to_vec(&ab).unwrap(),
+            serde_json::to_vec(&ba).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_same_editor_same_second_breaks_tie_by_content() {
+        let base = make_issue("st-a");
+        let mut local = edit(&base, "a@x", |i| {
+            i.labels = vec!["x".to_string()];
+            i.status = Status::Closed;
+        });
+        let mut remote = edit(&base, "a@x", |i| {
+            i.labels = vec!["y".to_string()];
+            i.reason = Some("dup".to_string());
+        });
+        local.updated_at = 200;
+        remote.updated_at = 200;
+
+        assert_eq!(merge_issue(&local, &remote), merge_issue(&remote, &local));
+    }
+
+    #[test]
+    fn test_concurrent_label_adds_both_survive() {
+        let base = make_issue("st-a");
+        let local = edit(&base, "a@x", |i| i.labels = vec!["ui".to_string()]);
+        let remote = edit(&base, "b@x", |i| i.labels = vec!["api".to_string()]);
+
+        let merged = merge_issue(&local, &remote);
+        assert_eq!(merged.labels, vec!["api", "ui"]);
+        assert_eq!(merged, merge_issue(&remote, &local));
+    }
+
+    #[test]
+    fn test_label_add_survives_concurrent_remove() {
+        let base = edit(&make_issue("st-a"), "a@x", |i| {
+            i.labels = vec!["bug".to_string()]
+        });
+        let removed = edit(&base, "a@x", |i| i.labels.clear());
+        let readded = edit(&base, "b@x", |i| i.labels.clear());
+        let readded = edit(&readded, "b@x", |i| i.labels = vec!["bug".to_string()]);
+
+        assert_eq!(merge_issue(&removed, &readded).labels, vec!["bug"]);
+
+        // A remove that has seen the add wins
+        let merged = merge_issue(&removed, &readded);
+        let removed_again = edit(&merged, "a@x", |i| i.labels.clear());
+        assert!(merge_issue(&readded, &removed_again).labels.is_empty());
+    }
+
+    #[test]
+    fn test_text_merge_keeps_edits_to_different_lines() {
+        let base = "intro\nmiddle\nend\n";
+        let (text, conflicted) = merge_text(
+            base,
+            ("intro, edited\nmiddle\nend\n", "a@x"),
+            ("intro\nmiddle\nend, edited\n", "b@x"),
+        );
+        assert_eq!(text, "intro, edited\nmiddle\nend, edited\n");
+        assert!(!conflicted);
+    }
+
+    #[test]
+    fn test_text_merge_keeps_both_appends() {
+        let base = "notes\n";
+        let (text, conflicted) =
+            merge_text(base, ("notes\nfrom a\n", "a@x"), ("notes\nfrom b", "b@x"));
+        assert_eq!(text, "notes\nfrom a\nfrom b");
+        assert!(!conflicted);
+    }
+
+    #[test]
+    fn test_text_merge_marks_conflicting_hunk() {
+        let (text, conflicted) = merge_text("one\ntwo\n", ("one\nA\n", "a@x"), ("one\nB\n", "b@x"));
+        assert!(conflicted);
+        assert_eq!(text, "one\n<<<<<<< a@x\nA\n=======\nB\n>>>>>>> b@x\n");
+        assert!(has_markers(&text));
+    }
+
+    #[test]
+    fn test_description_merge_with_base_converges() {
+        let base = edit(&make_issue("st-a"), "a@x", |i| {
+            i.description = "one\ntwo\n".to_string()
+        });
+        let local = edit(&base, "a@x", |i| {
+            i.description = "zero\none\ntwo\n".to_string()
+        });
+        let remote = edit(&base, "b@x", |i| {
+            i.description = "one\ntwo\nthree\n".to_string()
+        });
+
+        let ab = merge_issue_with_base(&local, &remote, Some(&base));
+        let ba = merge_issue_with_base(&remote, &local, Some(&base));
+        assert_eq!(ab, ba);
+        assert_eq!(ab.description, "zero\none\ntwo\nthree\n");
+        assert!(!ab.conflict);
+        assert!(ab.clocks.description > local.clocks.description);
+
+        let local = edit(&base, "a@x", |i| i.description = "one\nA\n".to_string());
+        let remote = edit(&base, "b@x", |i| i.description = "one\nB\n".to_string());
+        let merged = merge_issue_with_base(&local, &remote, Some(&base));
+        assert!(merged.conflict);
+        assert_eq!(merged, merge_issue_with_base(&remote, &local, Some(&base)));
+    }
+
+    #[test]
+    fn test_discarded_fields_only_concurrent_losers() {
+        let base = make_issue("st-a");
+        let local = edit(&base, "a@x", |i| {
+            i.title = "Local".to_string();
+            i.priority = Priority::High;
+        });
+        let remote = edit(&base, "b@x", |i| {
+            i.title = "Remote".to_string();
+            i.issue_type = IssueType::Bug;
+        });
+        let merged = merge_issue_with_base(&local, &remote, Some(&base));
+
+        // Priority and type were changed on one side only
+        assert_eq!(
+            discarded_fields(&local, &remote, &merged, Some(&base)),
+            vec!["title"]
+        );
+        assert_eq!(
+            discarded_fields(&local, &remote, &merged, None),
+            vec!["title", "priority", "type"]
+        );
+        assert!(discarded_fields(&local, &local, &local, Some(&base)).is_empty());
+    }
+
+    #[test]
+    fn test_conflict_resolution_wins_tie() {
+        let base = make_issue("st-a");
+        let open = Conflict {
+            schema_version: SCHEMA_VERSION,
+            id: base.id.clone(),
+            title: base.title.clone(),
+            fields: vec!["title".to_string()],
+            local: base.clone(),
+            remote: base.clone(),
+            source: "pull from origin".to_string(),
+            created_at: 0,
+            lamport: 3,
+            editor: "a@x".to_string(),
+            resolution: None,
+        };
+        let resolved = Conflict {
+            resolution: Some(Resolution::Merged),
+            ..open.clone()
+        };
+        assert_eq!(merge_conflict(&open, &resolved), resolved);
+        assert_eq!(merge_conflict(&resolved, &open), resolved);
+    }
+
+    #[test]
+    fn test_merge_is_idempotent() {
+        let base = make_issue("st-a");
+        let issue = edit(&base, "a@x", |i| i.priority = Priority::Low);
+        assert_eq!(merge_issue(&issue, &issue), issue);
+    }
+
+    #[test]
+    fn test_edge_removal_wins_tie() {
+        let live = Edge {
+            lamport: 2,
+            ..make_edge("st-a", "st-b", EdgeType::DependsOn)
+        };
+        let removed = Edge {
+            deleted: true,
+            ..live.clone()
+        };
+
+        assert!(merge_edge(&live, &removed).deleted);
+        assert!(merge_edge(&removed, &live).deleted);
+
+        let other = Edge {
+            editor: "b@x".to_string(),
+            created_at: 5,
+            ..live.clone()
+        };
+        assert_eq!(merge_edge(&live, &other), merge_edge(&other, &live));
+    }
+}
@@ -0,0 +1,311 @@
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::types::{LabelSet, SCHEMA_VERSION};
+
+/// Upgrades a payload from one schema version to the next, in place
+pub type Step = fn(&mut Value);
+
+/// `ISSUE_MIGRATIONS[n]` upgrades an issue from version `n + 1` to `n + 2`
+pub const ISSUE_MIGRATIONS: &[Step] = &[
+    issue_v1_to_v2,
+    issue_v2_to_v3,
+    issue_v3_to_v4,
+    issue_v4_to_v5,
+    issue_v5_to_v6,
+    issue_v6_to_v7,
+    issue_v7_to_v8,
+];
+
+/// `EDGE_MIGRATIONS[n]` upgrades an edge from version `n + 1` to `n + 2`
+pub const EDGE_MIGRATIONS: &[Step] = &[
+    edge_v1_to_v2,
+    edge_v2_to_v3,
+    edge_v3_to_v4,
+    edge_v4_to_v5,
+    edge_v5_to_v6,
+    edge_v6_to_v7,
+    edge_v7_to_v8,
+];
+
+/// Schema version conflict records were introduced in
+pub const CONFLICT_FIRST_VERSION: u32 = 7;
+
+/// `CONFLICT_MIGRATIONS[n]` upgrades a conflict record from version
+/// `n + CONFLICT_FIRST_VERSION` to the next. The issues embedded in a record
+/// are upgraded separately, with `ISSUE_MIGRATIONS`.
+pub const CONFLICT_MIGRATIONS: &[Step] = &[conflict_v7_to_v8];
+
+// Bumping SCHEMA_VERSION requires a migration step for every payload type
+const _: () = assert!(ISSUE_MIGRATIONS.len() == SCHEMA_VERSION as usize - 1);
+const _: () = assert!(EDGE_MIGRATIONS.len() == SCHEMA_VERSION as usize - 1);
+const _: () =
+    assert!(CONFLICT_MIGRATIONS.len() == (SCHEMA_VERSION - CONFLICT_FIRST_VERSION) as usize);
+
+/// v2 only changed edges
+fn issue_v1_to_v2(_value: &mut Value) {}
+
+/// v2 added removal tombstones with their own Lamport clock
+fn edge_v1_to_v2(value: &mut Value) {
+    value["lamport"] = Value::from(1);
+    value["editor"] = Value::from("");
+    value["deleted"] = Value::from(false);
+}
+
+/// v3 added issue deletion tombstones
+fn issue_v2_to_v3(value: &mut Value) {
+    value["deleted"] = Value::from(false);
+}
+
+/// v3 only changed issues
+fn edge_v2_to_v3(_value: &mut Value) {}
+
+/// v4 added per-field clocks, all starting at the issue's Lamport clock
+fn issue_v3_to_v4(value: &mut Value) {
+    let lamport = value["lamport"].clone();
+    value["clocks"] = serde_json::json!({
+        "title": lamport,
+        "description": lamport,
+        "status": lamport,
+        "priority": lamport,
+        "issue_type": lamport,
+        "labels": lamport,
+        "deleted": lamport,
+    });
+}
+
+/// v4 only changed issues
+fn edge_v3_to_v4(_value: &mut Value) {}
+
+/// v5 made labels an observed-remove set: every existing label gets one add
+/// tag from the issue's last edit, and the labels clock goes away
+fn issue_v4_to_v5(value: &mut Value) {
+    let tag = LabelSet::tag(
+        value["lamport"].as_u64().unwrap_or(0),
+        value["editor"].as_str().unwrap_or(""),
+    );
+    let mut tags = LabelSet::default();
+    for label in value["labels"].as_array().into_iter().flatten() {
+        if let Some(label) = label.as_str() {
+            tags.add(label, &tag);
+        }
+    }
+    value["labels"] = serde_json::json!(tags.labels());
+    value["label_tags"] = serde_json::json!(tags);
+    if let Some(clocks) = value["clocks"].as_object_mut() {
+        clocks.remove("labels");
+    }
+}
+
+/// v5 only changed issues
+fn edge_v4_to_v5(_value: &mut Value) {}
+
+/// v6 added the description conflict flag
+fn issue_v5_to_v6(value: &mut Value) {
+    value["conflict"] = Value::from(false);
+}
+
+/// v6 only changed issues
+fn edge_v5_to_v6(_value: &mut Value) {}
+
+/// v7 only changed edges
+fn issue_v6_to_v7(_value: &mut Value) {}
+
+/// v7 added edge quarantine
+fn edge_v6_to_v7(value: &mut Value) {
+    value["quarantine"] = Value::Null;
+}
+
+/// v8 recorded who holds a claim. Before, the claimant was taken to be the
+/// last editor, so that is the best guess for existing claims.
+fn issue_v7_to_v8(value: &mut Value) {
+    value["claimed_by"] = if value["claimed"].as_bool() == Some(true) {
+        value["editor"].clone()
+    } else {
+        Value::Null
+    };
+}
+
+/// v8 only changed issues
+fn edge_v7_to_v8(_value: &mut Value) {}
+
+/// v8 only changed issues, which `upgrade_conflict` upgrades on their own
+fn conflict_v7_to_v8(_value: &mut Value) {}
+
+/// Schema version stored in a payload (0 if missing)
+pub fn version_of(value: &Value) -> u32 {
+    value["schema_version"].as_u64().unwrap_or(0) as u32
+}
+
+/// Bring an issue payload up to the current schema version
+pub fn upgrade_issue(value: &mut Value) -> Result<(), Error> {
+    upgrade(value, SCHEMA_VERSION, ISSUE_MIGRATIONS)
+}
+
+/// Bring an edge payload up to the current schema version
+pub fn upgrade_edge(value: &mut Value) -> Result<(), Error> {
+    upgrade(value, SCHEMA_VERSION, EDGE_MIGRATIONS)
+}
+
+/// Bring a conflict record up to the current schema version, along with the
+/// local and remote issues it embeds
+pub fn upgrade_conflict(value: &mut Value) -> Result<(), Error> {
+    upgrade_from(
+        value,
+        CONFLICT_FIRST_VERSION,
+        SCHEMA_VERSION,
+        CONFLICT_MIGRATIONS,
+    )?;
+    for side in ["local", "remote"] {
+        upgrade_issue(&mut value[side])?;
+    }
+    Ok(())
+}
+
+/// Run every step between the payload's version and `target`.
+/// Payloads newer than `target` were written by a newer sterna and are rejected.
+fn upgrade(value: &mut Value, target: u32, steps: &[Step]) -> Result<(), Error> {
+    upgrade_from(value, 1, target, steps)
+}
+
+/// `upgrade` for a payload type that first appeared at schema version `first`,
+/// so `steps[0]` upgrades from `first`
+fn upgrade_from(value: &mut Value, first: u32, target: u32, steps: &[Step]) -> Result<(), Error> {
+    let version = version_of(value);
+    if version > target {
+        return Err(Error::SchemaTooNew {
+            supported: target,
+            found: version,
+        });
+    }
+    if version < first {
+        return Err(Error::SchemaMismatch {
+            expected: target,
+            found: version,
+        });
+    }
+
+    for step in &steps[(version - first) as usize..(target - first) as usize] {
+        step(value);
+    }
+    value["schema_version"] = Value::from(target);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn add_flag(value: &mut Value) {
+        value["flag"] = Value::from(false);
+    }
+
+    fn rename_flag(value: &mut Value) {
+        let flag = value["flag"].take();
+        value["enabled"] = flag;
+        value.as_object_mut().unwrap().remove("flag");
+    }
+
+    const STEPS: &[Step] = &[add_flag, rename_flag];
+
+    #[test]
+    fn test_upgrade_runs_steps_in_order() {
+        let mut value = json!({"schema_version": 1});
+        upgrade(&mut value, 3, STEPS).unwrap();
+        assert_eq!(value, json!({"schema_version": 3, "enabled": false}));
+    }
+
+    #[test]
+    fn test_upgrade_skips_applied_steps() {
+        let mut value = json!({"schema_version": 2, "flag": true});
+        upgrade(&mut value, 3, STEPS).unwrap();
+        assert_eq!(value, json!({"schema_version": 3, "enabled": true}));
+    }
+
+    #[test]
+    fn test_upgrade_current_is_noop() {
+        let mut value = json!({"schema_version": 3, "enabled": true});
+        upgrade(&mut value, 3, STEPS).unwrap();
+        assert_eq!(value, json!({"schema_version": 3, "enabled": true}));
+    }
+
+    #[test]
+    fn test_upgrade_rejects_newer() {
+        let mut value = json!({"schema_version": 4});
+        assert!(matches!(
+            upgrade(&mut value, 3, STEPS),
+            Err(Error::SchemaTooNew {
+                supported: 3,
+                found: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn test_edge_v1_gains_tombstone_fields() {
+        let mut value = json!({"schema_version": 1, "source": "st-a", "target": "st-b"});
+        upgrade_edge(&mut value).unwrap();
+        assert_eq!(value["lamport"], 1);
+        assert_eq!(value["deleted"], false);
+        assert_eq!(value["quarantine"], Value::Null);
+        assert_eq!(version_of(&value), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_issue_v4_labels_become_tagged() {
+        let mut value = json!({
+            "schema_version": 4,
+            "labels": ["ui", "api"],
+            "lamport": 7,
+            "editor": "a@x",
+            "clocks": {"labels": 7, "title": 7},
+        });
+        upgrade_issue(&mut value).unwrap();
+        assert_eq!(value["labels"], json!(["api", "ui"]));
+        assert_eq!(value["label_tags"]["ui"]["added"], json!(["7:a@x"]));
+        assert_eq!(value["clocks"], json!({"title": 7}));
+    }
+
+    #[test]
+    fn test_issue_v7_claim_goes_to_last_editor() {
+        let mut claimed = json!({"schema_version": 7, "claimed": true, "editor": "a@x"});
+        upgrade_issue(&mut claimed).unwrap();
+        assert_eq!(claimed["claimed_by"], "a@x");
+
+        let mut open = json!({"schema_version": 7, "claimed": false, "editor": "a@x"});
+        upgrade_issue(&mut open).unwrap();
+        assert_eq!(open["claimed_by"], Value::Null);
+    }
+
+    #[test]
+    fn test_conflict_upgrades_embedded_issues() {
+        let mut value = json!({
+            "schema_version": CONFLICT_FIRST_VERSION,
+            "local": {"schema_version": 5},
+            "remote": {"schema_version": SCHEMA_VERSION},
+        });
+        upgrade_conflict(&mut value).unwrap();
+        assert_eq!(version_of(&value), SCHEMA_VERSION);
+        assert_eq!(version_of(&value["local"]), SCHEMA_VERSION);
+        assert_eq!(value["local"]["conflict"], false);
+    }
+
+    #[test]
+    fn test_upgrade_from_rejects_before_first() {
+        let mut value = json!({"schema_version": 1});
+        assert!(matches!(
+            upgrade_from(&mut value, 2, 3, &STEPS[1..]),
+            Err(Error::SchemaMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_upgrade_rejects_missing_version() {
+        let mut value = json!({});
+        assert!(matches!(
+            upgrade(&mut value, 3, STEPS),
+            Err(Error::SchemaMismatch { .. })
+        ));
+    }
+}
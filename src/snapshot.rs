@@ -2,12 +2,30 @@ use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 
 use fs2::FileExt;
-use git2::{Commit, Repository, Tree};
+use git2::{Commit, ErrorCode, Oid, Repository, Sort, Tree};
 
+use crate::date;
 use crate::error::Error;
-use crate::types::{Edge, EdgeType, Issue};
+use crate::types::{Conflict, Edge, EdgeType, Issue, SCHEMA_VERSION, Snapshot};
 
-const SNAPSHOT_REF: &str = "refs/sterna/snapshot";
+pub(crate) const SNAPSHOT_REF: &str = "refs/sterna/snapshot";
+
+/// Last snapshot commit taken over from a remote as-is (fast-forward pull or
+/// `init --from`). It and its ancestors came from elsewhere, even where they
+/// are on the first-parent history, so `st undo` does not go past it.
+const PULLED_REF: &str = "refs/sterna/pulled";
+
+/// Remote-tracking ref holding the last snapshot fetched from `remote_name`
+pub fn remote_ref(remote_name: &str) -> String {
+    format!("refs/sterna/remotes/{remote_name}/snapshot")
+}
+
+/// The last snapshot commit fetched from `remote_name`, if any
+pub fn remote_commit<'a>(repo: &'a Repository, remote_name: &str) -> Option<Commit<'a>> {
+    repo.find_reference(&remote_ref(remote_name))
+        .and_then(|r| r.peel_to_commit())
+        .ok()
+}
 
 /// Advisory lock for snapshot operations
 pub struct SnapshotLock {
@@ -35,20 +53,18 @@ pub fn is_initialized(repo: &Repository) -> bool {
 }
 
 /// Get the current snapshot commit, if any
-fn get_snapshot_commit(repo: &Repository) -> Result<Commit, Error> {
+pub(crate) fn get_snapshot_commit(repo: &Repository) -> Result<Commit<'_>, Error> {
     let reference = repo.find_reference(SNAPSHOT_REF)?;
     let commit = reference.peel_to_commit()?;
     Ok(commit)
 }
 
-/// Get the current snapshot tree
-fn get_snapshot_tree(repo: &Repository) -> Result<Tree, Error> {
-    let commit = get_snapshot_commit(repo)?;
-    Ok(commit.tree()?)
-}
-
 /// Get a subtree by name from a parent tree
-fn get_subtree<'a>(repo: &'a Repository, tree: &Tree, name: &str) -> Result<Tree<'a>, Error> {
+pub(crate) fn get_subtree<'a>(
+    repo: &'a Repository,
+    tree: &Tree,
+    name: &str,
+) -> Result<Tree<'a>, Error> {
     let entry = tree
         .get_name(name)
         .ok_or_else(|| Error::CorruptedSnapshot(format!("missing {name} subtree")))?;
@@ -57,23 +73,27 @@ fn get_subtree<'a>(repo: &'a Repository, tree: &Tree, name: &str) -> Result<Tree
         .map_err(|e| Error::CorruptedSnapshot(format!("{name} is not a tree: {e}")))
 }
 
+/// Get a subtree that older snapshots may not have, such as `conflicts`
+pub(crate) fn get_optional_subtree<'a>(
+    repo: &'a Repository,
+    tree: &Tree,
+    name: &str,
+) -> Result<Option<Tree<'a>>, Error> {
+    if tree.get_name(name).is_none() {
+        return Ok(None);
+    }
+    get_subtree(repo, tree, name).map(Some)
+}
+
 /// Initialize Sterna - creates empty snapshot with issues/ and edges/ subtrees
+/// and a fresh `meta` blob
 pub fn init(repo: &Repository) -> Result<(), Error> {
     if is_initialized(repo) {
         return Err(Error::AlreadyInitialized);
     }
 
-    let issues_builder = repo.treebuilder(None)?;
-    let issues_oid = issues_builder.write()?;
-
-    let edges_builder = repo.treebuilder(None)?;
-    let edges_oid = edges_builder.write()?;
-
-    let mut root_builder = repo.treebuilder(None)?;
-    root_builder.insert("issues", issues_oid, 0o040000)?;
-    root_builder.insert("edges", edges_oid, 0o040000)?;
-    let tree_oid = root_builder.write()?;
-
+    let empty = repo.treebuilder(None)?.write()?;
+    let tree_oid = write_root(repo, empty, empty, None, &Snapshot::default(), 0)?;
     let tree = repo.find_tree(tree_oid)?;
     let sig = repo.signature()?;
 
@@ -89,13 +109,167 @@ pub fn init(repo: &Repository) -> Result<(), Error> {
     Ok(())
 }
 
-/// Load all issues from the snapshot
+/// Initialize from a snapshot made elsewhere by pointing the ref at `commit`
+/// as-is, so later pulls and pushes share its history
+pub fn adopt(repo: &Repository, commit: &Commit) -> Result<(), Error> {
+    let _lock = SnapshotLock::acquire(repo)?;
+    if is_initialized(repo) {
+        return Err(Error::AlreadyInitialized);
+    }
+
+    let tree = commit.tree()?;
+    get_subtree(repo, &tree, "issues")?;
+    get_subtree(repo, &tree, "edges")?;
+    repo.reference(
+        SNAPSHOT_REF,
+        commit.id(),
+        false,
+        &format!("sterna: adopt {}", commit.id()),
+    )?;
+    mark_pulled(repo, commit.id())?;
+    Ok(())
+}
+
+/// Read the `meta` blob of the current snapshot
+pub fn load_meta(repo: &Repository) -> Result<Snapshot, Error> {
+    if !is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let commit = get_snapshot_commit(repo)?;
+    load_meta_at(repo, &commit)
+}
+
+/// Read the `meta` blob of a snapshot commit. Snapshots written before it
+/// existed (or with an unreadable one) get it rebuilt from their contents.
+pub fn load_meta_at(repo: &Repository, commit: &Commit) -> Result<Snapshot, Error> {
+    let tree = commit.tree()?;
+    match stored_meta(repo, &tree) {
+        Some(meta) => Ok(meta),
+        None => rebuild_meta(repo, &tree),
+    }
+}
+
+/// The `meta` blob as stored in a snapshot tree, if present and readable
+pub(crate) fn stored_meta(repo: &Repository, tree: &Tree) -> Option<Snapshot> {
+    let entry = tree.get_name("meta")?;
+    let blob = repo.find_blob(entry.id()).ok()?;
+    Snapshot::from_json(blob.content()).ok()
+}
+
+/// Compute `meta` from a snapshot tree. Blobs that cannot be read are skipped
+/// so this works on trees `st fsck` is about to repair.
+pub(crate) fn rebuild_meta(repo: &Repository, tree: &Tree) -> Result<Snapshot, Error> {
+    let issues_tree = get_subtree(repo, tree, "issues")?;
+    let edges_tree = get_subtree(repo, tree, "edges")?;
+    let conflicts_tree = get_optional_subtree(repo, tree, "conflicts")?;
+    let lamport = issues_tree
+        .iter()
+        .chain(edges_tree.iter())
+        .chain(conflicts_tree.iter().flat_map(|t| t.iter()))
+        .filter_map(|entry| repo.find_blob(entry.id()).ok())
+        .filter_map(|blob| serde_json::from_slice::<serde_json::Value>(blob.content()).ok())
+        .filter_map(|value| value["lamport"].as_u64())
+        .max()
+        .unwrap_or(0);
+    Ok(build_meta(
+        &Snapshot::default(),
+        &issues_tree,
+        &edges_tree,
+        conflicts_tree.as_ref(),
+        lamport,
+    ))
+}
+
+/// The repo-wide Lamport clock. Local edits move past it so they dominate
+/// everything this clone has written or pulled.
+pub fn clock(repo: &Repository) -> Result<u64, Error> {
+    Ok(load_meta(repo)?.lamport)
+}
+
+pub(crate) fn build_meta(
+    prev: &Snapshot,
+    issues_tree: &Tree,
+    edges_tree: &Tree,
+    conflicts_tree: Option<&Tree>,
+    lamport: u64,
+) -> Snapshot {
+    let hashes = |tree: &Tree| tree.iter().map(|e| e.id().to_string()).collect();
+    Snapshot {
+        schema_version: SCHEMA_VERSION,
+        version: prev.version + 1,
+        created_at: chrono::Utc::now().timestamp(),
+        lamport: prev.lamport.max(lamport),
+        issue_hashes: hashes(issues_tree),
+        edge_hashes: hashes(edges_tree),
+        conflict_hashes: conflicts_tree.map(hashes).unwrap_or_default(),
+    }
+}
+
+/// Write a root tree with the given subtrees and a `meta` blob derived from
+/// `prev`, witnessing `lamport`. `conflicts/` is only written once a merge
+/// has recorded something in it.
+fn write_root(
+    repo: &Repository,
+    issues_oid: Oid,
+    edges_oid: Oid,
+    conflicts_oid: Option<Oid>,
+    prev: &Snapshot,
+    lamport: u64,
+) -> Result<Oid, Error> {
+    let conflicts_tree = conflicts_oid.map(|oid| repo.find_tree(oid)).transpose()?;
+    let meta = build_meta(
+        prev,
+        &repo.find_tree(issues_oid)?,
+        &repo.find_tree(edges_oid)?,
+        conflicts_tree.as_ref(),
+        lamport,
+    );
+    let meta_oid = repo.blob(&serde_json::to_vec(&meta)?)?;
+
+    let mut root_builder = repo.treebuilder(None)?;
+    root_builder.insert("issues", issues_oid, 0o040000)?;
+    root_builder.insert("edges", edges_oid, 0o040000)?;
+    if let Some(conflicts_oid) = conflicts_oid {
+        root_builder.insert("conflicts", conflicts_oid, 0o040000)?;
+    }
+    root_builder.insert("meta", meta_oid, 0o100644)?;
+    Ok(root_builder.write()?)
+}
+
+/// Load all live issues from the snapshot
 pub fn load_issues(repo: &Repository) -> Result<HashMap<String, Issue>, Error> {
     if !is_initialized(repo) {
         return Err(Error::NotInitialized);
     }
 
-    let tree = get_snapshot_tree(repo)?;
+    let commit = get_snapshot_commit(repo)?;
+    load_issues_at(repo, &commit)
+}
+
+/// Load all live issues as they were in a given snapshot commit
+pub fn load_issues_at(repo: &Repository, commit: &Commit) -> Result<HashMap<String, Issue>, Error> {
+    let mut issues = load_all_issues_at(repo, commit)?;
+    issues.retain(|_, issue| !issue.deleted);
+    Ok(issues)
+}
+
+/// Load all issues including deletion tombstones
+pub fn load_all_issues(repo: &Repository) -> Result<HashMap<String, Issue>, Error> {
+    if !is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let commit = get_snapshot_commit(repo)?;
+    load_all_issues_at(repo, &commit)
+}
+
+/// Load all issues including deletion tombstones from a given snapshot commit
+pub fn load_all_issues_at(
+    repo: &Repository,
+    commit: &Commit,
+) -> Result<HashMap<String, Issue>, Error> {
+    let tree = commit.tree()?;
     let issues_tree = get_subtree(repo, &tree, "issues")?;
 
     let mut issues = HashMap::new();
@@ -112,10 +286,97 @@ pub fn load_issues(repo: &Repository) -> Result<HashMap<String, Issue>, Error> {
     Ok(issues)
 }
 
+/// Resolve `--at` to a snapshot commit. Accepts a date, which picks the
+/// newest snapshot commit at or before it, or anything git can resolve to a
+/// commit. Bare digits are read as an abbreviated hash; a Unix timestamp
+/// takes git's `@` prefix.
+pub fn resolve_at<'a>(repo: &'a Repository, spec: &str) -> Result<Commit<'a>, Error> {
+    if !is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let is_hash_like = spec.bytes().all(|b| b.is_ascii_digit());
+    if !is_hash_like && let Ok(timestamp) = date::parse_date(spec) {
+        return history(repo)?
+            .into_iter()
+            .rev()
+            .find(|commit| commit.time().seconds() <= timestamp)
+            .ok_or_else(|| Error::NoSnapshotAt(spec.to_string()));
+    }
+
+    let Ok(commit) = repo
+        .revparse_single(spec)
+        .and_then(|obj| obj.peel_to_commit())
+    else {
+        return Err(Error::InvalidDate(spec.to_string()));
+    };
+    let tree = commit.tree()?;
+    get_subtree(repo, &tree, "issues")?;
+    get_subtree(repo, &tree, "edges")?;
+    Ok(commit)
+}
+
+/// Snapshot commits from oldest to newest, following first parents
+pub fn history(repo: &Repository) -> Result<Vec<Commit<'_>>, Error> {
+    if !is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let mut walk = repo.revwalk()?;
+    walk.push_ref(SNAPSHOT_REF)?;
+    walk.simplify_first_parent()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+
+    let mut commits = Vec::new();
+    for oid in walk {
+        commits.push(repo.find_commit(oid?)?);
+    }
+    Ok(commits)
+}
+
+/// Every snapshot commit reachable from the ref, oldest first
+pub fn all_commits(repo: &Repository) -> Result<Vec<Commit<'_>>, Error> {
+    if !is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let mut walk = repo.revwalk()?;
+    walk.push_ref(SNAPSHOT_REF)?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME | Sort::REVERSE)?;
+
+    let mut commits = Vec::new();
+    for oid in walk {
+        commits.push(repo.find_commit(oid?)?);
+    }
+    Ok(commits)
+}
+
+/// Blob ID of an issue in a snapshot tree, if present
+pub fn issue_blob_id(tree: &Tree, id: &str) -> Option<Oid> {
+    tree.get_path(std::path::Path::new("issues").join(id).as_path())
+        .ok()
+        .map(|entry| entry.id())
+}
+
+/// Load an issue blob by ID
+pub fn read_issue_blob(repo: &Repository, oid: Oid) -> Result<Issue, Error> {
+    let blob = repo.find_blob(oid)?;
+    Issue::from_json(blob.content())
+}
+
 /// Load a single issue by ID (or prefix)
 pub fn load_issue(repo: &Repository, id_prefix: &str) -> Result<Issue, Error> {
     let issues = load_issues(repo)?;
+    find_issue(&issues, id_prefix)
+}
+
+/// Load a single issue by ID (or prefix) as it was in a given snapshot commit
+pub fn load_issue_at(repo: &Repository, commit: &Commit, id_prefix: &str) -> Result<Issue, Error> {
+    let issues = load_issues_at(repo, commit)?;
+    find_issue(&issues, id_prefix)
+}
 
+fn find_issue(issues: &HashMap<String, Issue>, id_prefix: &str) -> Result<Issue, Error> {
     let matches: Vec<_> = issues
         .iter()
         .filter(|(id, _)| id.starts_with(id_prefix))
@@ -133,8 +394,15 @@ pub fn load_issue(repo: &Repository, id_prefix: &str) -> Result<Issue, Error> {
 
 /// Find unique issue ID from prefix
 pub fn find_issue_id(repo: &Repository, id_prefix: &str) -> Result<String, Error> {
-    let issues = load_issues(repo)?;
+    match_issue_id(&load_issues(repo)?, id_prefix)
+}
+
+/// Find unique issue ID from prefix, deleted issues included
+pub fn find_any_issue_id(repo: &Repository, id_prefix: &str) -> Result<String, Error> {
+    match_issue_id(&load_all_issues(repo)?, id_prefix)
+}
 
+fn match_issue_id(issues: &HashMap<String, Issue>, id_prefix: &str) -> Result<String, Error> {
     let matches: Vec<_> = issues
         .keys()
         .filter(|id| id.starts_with(id_prefix))
@@ -156,7 +424,30 @@ pub fn load_edges(repo: &Repository) -> Result<Vec<Edge>, Error> {
         return Err(Error::NotInitialized);
     }
 
-    let tree = get_snapshot_tree(repo)?;
+    let commit = get_snapshot_commit(repo)?;
+    load_edges_at(repo, &commit)
+}
+
+/// Load all live edges as they were in a given snapshot commit
+pub fn load_edges_at(repo: &Repository, commit: &Commit) -> Result<Vec<Edge>, Error> {
+    let mut edges = load_all_edges_at(repo, commit)?;
+    edges.retain(Edge::is_live);
+    Ok(edges)
+}
+
+/// Load all edges including removal tombstones
+pub fn load_all_edges(repo: &Repository) -> Result<Vec<Edge>, Error> {
+    if !is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let commit = get_snapshot_commit(repo)?;
+    load_all_edges_at(repo, &commit)
+}
+
+/// Load all edges including removal tombstones from a given snapshot commit
+pub fn load_all_edges_at(repo: &Repository, commit: &Commit) -> Result<Vec<Edge>, Error> {
+    let tree = commit.tree()?;
     let edges_tree = get_subtree(repo, &tree, "edges")?;
 
     let mut edges = Vec::new();
@@ -169,6 +460,31 @@ pub fn load_edges(repo: &Repository) -> Result<Vec<Edge>, Error> {
     Ok(edges)
 }
 
+/// Load every conflict record, resolved ones included
+pub fn load_conflicts(repo: &Repository) -> Result<Vec<Conflict>, Error> {
+    if !is_initialized(repo) {
+        return Err(Error::NotInitialized);
+    }
+
+    let commit = get_snapshot_commit(repo)?;
+    load_conflicts_at(repo, &commit)
+}
+
+/// Load every conflict record from a given snapshot commit
+pub fn load_conflicts_at(repo: &Repository, commit: &Commit) -> Result<Vec<Conflict>, Error> {
+    let tree = commit.tree()?;
+    let Some(conflicts_tree) = get_optional_subtree(repo, &tree, "conflicts")? else {
+        return Ok(Vec::new());
+    };
+
+    let mut conflicts = Vec::new();
+    for entry in conflicts_tree.iter() {
+        let blob = repo.find_blob(entry.id())?;
+        conflicts.push(Conflict::from_json(blob.content())?);
+    }
+    Ok(conflicts)
+}
+
 /// Check if an edge already exists
 pub fn edge_exists(
     repo: &Repository,
@@ -184,88 +500,51 @@ pub fn edge_exists(
 
 /// Save an issue (create or update)
 pub fn save_issue(repo: &Repository, issue: &Issue, message: &str) -> Result<(), Error> {
-    if !is_initialized(repo) {
-        return Err(Error::NotInitialized);
-    }
-    let _lock = SnapshotLock::acquire(repo)?;
-
-    let current_commit = get_snapshot_commit(repo)?;
-    let current_tree = current_commit.tree()?;
-    let issues_tree = get_subtree(repo, &current_tree, "issues")?;
-    let edges_tree = get_subtree(repo, &current_tree, "edges")?;
-
-    let blob_content = serde_json::to_vec(issue)?;
-    let blob_oid = repo.blob(&blob_content)?;
-
-    let mut issues_builder = repo.treebuilder(Some(&issues_tree))?;
-    issues_builder.insert(&issue.id, blob_oid, 0o100644)?;
-    let new_issues_oid = issues_builder.write()?;
-
-    // Build new root tree (keeping edges unchanged)
-    let mut root_builder = repo.treebuilder(None)?;
-    root_builder.insert("issues", new_issues_oid, 0o040000)?;
-    root_builder.insert("edges", edges_tree.id(), 0o040000)?;
-    let new_tree_oid = root_builder.write()?;
-    let new_tree = repo.find_tree(new_tree_oid)?;
-    let sig = repo.signature()?;
-
-    repo.commit(
-        Some(SNAPSHOT_REF),
-        &sig,
-        &sig,
-        message,
-        &new_tree,
-        &[&current_commit],
-    )?;
-
-    Ok(())
+    let changes = Changeset {
+        issues: vec![issue.clone()],
+        ..Default::default()
+    };
+    commit_changes(repo, &changes, message)
 }
 
 /// Save an edge
 pub fn save_edge(repo: &Repository, edge: &Edge, message: &str) -> Result<(), Error> {
-    if !is_initialized(repo) {
-        return Err(Error::NotInitialized);
-    }
-    let _lock = SnapshotLock::acquire(repo)?;
-
-    let current_commit = get_snapshot_commit(repo)?;
-    let current_tree = current_commit.tree()?;
-    let issues_tree = get_subtree(repo, &current_tree, "issues")?;
-    let edges_tree = get_subtree(repo, &current_tree, "edges")?;
-
-    let blob_content = serde_json::to_vec(edge)?;
-    let blob_oid = repo.blob(&blob_content)?;
-
-    // Edge filename: source_target_type
-    let edge_name = format!(
-        "{}_{}_{}",
-        edge.source,
-        edge.target,
-        edge.edge_type.as_str()
-    );
-
-    let mut edges_builder = repo.treebuilder(Some(&edges_tree))?;
-    edges_builder.insert(&edge_name, blob_oid, 0o100644)?;
-    let new_edges_oid = edges_builder.write()?;
-
-    // Build new root tree (keeping issues unchanged)
-    let mut root_builder = repo.treebuilder(None)?;
-    root_builder.insert("issues", issues_tree.id(), 0o040000)?;
-    root_builder.insert("edges", new_edges_oid, 0o040000)?;
-    let new_tree_oid = root_builder.write()?;
-    let new_tree = repo.find_tree(new_tree_oid)?;
-    let sig = repo.signature()?;
+    let changes = Changeset {
+        edges: vec![edge.clone()],
+        ..Default::default()
+    };
+    commit_changes(repo, &changes, message)
+}
 
-    repo.commit(
-        Some(SNAPSHOT_REF),
-        &sig,
-        &sig,
-        message,
-        &new_tree,
-        &[&current_commit],
-    )?;
+/// A batch of writes applied to the snapshot in a single commit
+#[derive(Debug, Clone, Default)]
+pub struct Changeset {
+    pub issues: Vec<Issue>,
+    pub edges: Vec<Edge>,
+    /// Conflict records to write under `conflicts/`
+    pub conflicts: Vec<Conflict>,
+    /// Tree entry names under `issues/` to remove
+    pub removed_issues: Vec<String>,
+    /// Tree entry names under `edges/` to remove
+    pub removed_edges: Vec<String>,
+    /// Tree entry names under `conflicts/` to remove
+    pub removed_conflicts: Vec<String>,
+    /// Lamport value seen elsewhere (e.g. a remote's clock) to fold into the
+    /// repo-wide clock
+    pub witness: u64,
+    /// Second parent of the commit, making it a merge (pull)
+    pub merge_parent: Option<Oid>,
+}
 
-    Ok(())
+impl Changeset {
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+            && self.edges.is_empty()
+            && self.conflicts.is_empty()
+            && self.removed_issues.is_empty()
+            && self.removed_edges.is_empty()
+            && self.removed_conflicts.is_empty()
+    }
 }
 
 /// Merge multiple issues and edges in a single commit
@@ -275,21 +554,58 @@ pub fn merge_snapshot(
     edges: &[Edge],
     message: &str,
 ) -> Result<(), Error> {
+    let changes = Changeset {
+        issues: issues.to_vec(),
+        edges: edges.to_vec(),
+        ..Default::default()
+    };
+    commit_changes(repo, &changes, message)
+}
+
+/// Apply a changeset in a single commit, keeping `meta` up to date. Does
+/// nothing if it is empty, witnesses nothing new and merges nothing.
+pub fn commit_changes(repo: &Repository, changes: &Changeset, message: &str) -> Result<(), Error> {
     if !is_initialized(repo) {
         return Err(Error::NotInitialized);
     }
-    if issues.is_empty() && edges.is_empty() {
-        return Ok(());
-    }
     let _lock = SnapshotLock::acquire(repo)?;
 
     let current_commit = get_snapshot_commit(repo)?;
     let current_tree = current_commit.tree()?;
     let issues_tree = get_subtree(repo, &current_tree, "issues")?;
     let edges_tree = get_subtree(repo, &current_tree, "edges")?;
+    let conflicts_tree = get_optional_subtree(repo, &current_tree, "conflicts")?;
+
+    // A missing or stale meta blob is rewritten even without other changes
+    let stored = stored_meta(repo, &current_tree);
+    let meta_current = stored.as_ref().is_some_and(|m| {
+        m.same_content(&build_meta(
+            m,
+            &issues_tree,
+            &edges_tree,
+            conflicts_tree.as_ref(),
+            m.lamport,
+        ))
+    });
+    let meta = match stored {
+        Some(meta) => meta,
+        None => rebuild_meta(repo, &current_tree)?,
+    };
+    if changes.is_empty()
+        && changes.witness <= meta.lamport
+        && meta_current
+        && changes.merge_parent.is_none()
+    {
+        return Ok(());
+    }
 
     let mut issues_builder = repo.treebuilder(Some(&issues_tree))?;
-    for issue in issues {
+    for name in &changes.removed_issues {
+        if issues_builder.get(name)?.is_some() {
+            issues_builder.remove(name)?;
+        }
+    }
+    for issue in &changes.issues {
         let blob_content = serde_json::to_vec(issue)?;
         let blob_oid = repo.blob(&blob_content)?;
         issues_builder.insert(&issue.id, blob_oid, 0o100644)?;
@@ -297,89 +613,127 @@ pub fn merge_snapshot(
     let new_issues_oid = issues_builder.write()?;
 
     let mut edges_builder = repo.treebuilder(Some(&edges_tree))?;
-    for edge in edges {
-        let edge_name = format!(
-            "{}_{}_{}",
-            edge.source,
-            edge.target,
-            edge.edge_type.as_str()
-        );
+    for name in &changes.removed_edges {
+        if edges_builder.get(name)?.is_some() {
+            edges_builder.remove(name)?;
+        }
+    }
+    for edge in &changes.edges {
         let blob_content = serde_json::to_vec(edge)?;
         let blob_oid = repo.blob(&blob_content)?;
-        edges_builder.insert(&edge_name, blob_oid, 0o100644)?;
+        edges_builder.insert(edge_name(edge), blob_oid, 0o100644)?;
     }
     let new_edges_oid = edges_builder.write()?;
 
-    let mut root_builder = repo.treebuilder(None)?;
-    root_builder.insert("issues", new_issues_oid, 0o040000)?;
-    root_builder.insert("edges", new_edges_oid, 0o040000)?;
-    let new_tree_oid = root_builder.write()?;
+    let new_conflicts_oid = if conflicts_tree.is_some() || !changes.conflicts.is_empty() {
+        let mut conflicts_builder = repo.treebuilder(conflicts_tree.as_ref())?;
+        for name in &changes.removed_conflicts {
+            if conflicts_builder.get(name)?.is_some() {
+                conflicts_builder.remove(name)?;
+            }
+        }
+        for conflict in &changes.conflicts {
+            let blob_oid = repo.blob(&serde_json::to_vec(conflict)?)?;
+            conflicts_builder.insert(conflict_name(conflict), blob_oid, 0o100644)?;
+        }
+        Some(conflicts_builder.write()?)
+    } else {
+        None
+    };
+
+    let lamport = changes
+        .issues
+        .iter()
+        .map(|i| i.lamport)
+        .chain(changes.edges.iter().map(|e| e.lamport))
+        .chain(changes.conflicts.iter().map(|c| c.lamport))
+        .fold(changes.witness, u64::max);
+    let new_tree_oid = write_root(
+        repo,
+        new_issues_oid,
+        new_edges_oid,
+        new_conflicts_oid,
+        &meta,
+        lamport,
+    )?;
     let new_tree = repo.find_tree(new_tree_oid)?;
     let sig = repo.signature()?;
 
-    repo.commit(
-        Some(SNAPSHOT_REF),
-        &sig,
-        &sig,
-        message,
-        &new_tree,
-        &[&current_commit],
-    )?;
+    let merge_parent = changes
+        .merge_parent
+        .map(|oid| repo.find_commit(oid))
+        .transpose()?;
+    let mut parents = vec![&current_commit];
+    parents.extend(merge_parent.as_ref());
+
+    repo.commit(Some(SNAPSHOT_REF), &sig, &sig, message, &new_tree, &parents)?;
 
     Ok(())
 }
 
-/// Delete an edge
-pub fn delete_edge(
-    repo: &Repository,
-    source: &str,
-    target: &str,
-    edge_type: EdgeType,
-    message: &str,
-) -> Result<bool, Error> {
-    if !is_initialized(repo) {
-        return Err(Error::NotInitialized);
-    }
+/// Move the snapshot ref from `from` forward to `target`, a descendant of
+/// it. Returns false and leaves the ref alone if another write moved it off
+/// `from` in the meantime.
+pub fn fast_forward(repo: &Repository, from: Oid, target: &Commit) -> Result<bool, Error> {
     let _lock = SnapshotLock::acquire(repo)?;
-
-    let current_commit = get_snapshot_commit(repo)?;
-    let current_tree = current_commit.tree()?;
-    let issues_tree = get_subtree(repo, &current_tree, "issues")?;
-    let edges_tree = get_subtree(repo, &current_tree, "edges")?;
-
-    let edge_name = format!("{}_{}_{}", source, target, edge_type.as_str());
-
-    if edges_tree.get_name(&edge_name).is_none() {
-        return Ok(false);
+    match repo.reference_matching(
+        SNAPSHOT_REF,
+        target.id(),
+        true,
+        from,
+        &format!("sterna: fast-forward to {}", target.id()),
+    ) {
+        Ok(_) => {}
+        Err(e) if e.code() == ErrorCode::Modified => return Ok(false),
+        Err(e) => return Err(e.into()),
     }
+    mark_pulled(repo, target.id())?;
+    Ok(true)
+}
 
-    let mut edges_builder = repo.treebuilder(Some(&edges_tree))?;
-    edges_builder.remove(&edge_name)?;
-    let new_edges_oid = edges_builder.write()?;
+fn mark_pulled(repo: &Repository, commit: Oid) -> Result<(), Error> {
+    repo.reference(
+        PULLED_REF,
+        commit,
+        true,
+        &format!("sterna: pulled {commit}"),
+    )?;
+    Ok(())
+}
 
-    let mut root_builder = repo.treebuilder(None)?;
-    root_builder.insert("issues", issues_tree.id(), 0o040000)?;
-    root_builder.insert("edges", new_edges_oid, 0o040000)?;
-    let new_tree_oid = root_builder.write()?;
-    let new_tree = repo.find_tree(new_tree_oid)?;
-    let sig = repo.signature()?;
+/// Whether a snapshot commit came in with a pull that fast-forwarded (or an
+/// adopt), i.e. it is the last such head or one of its ancestors
+pub fn is_pulled(repo: &Repository, commit: Oid) -> Result<bool, Error> {
+    let Ok(pulled) = repo.refname_to_id(PULLED_REF) else {
+        return Ok(false);
+    };
+    Ok(pulled == commit || repo.graph_descendant_of(pulled, commit)?)
+}
 
-    repo.commit(
-        Some(SNAPSHOT_REF),
-        &sig,
-        &sig,
-        message,
-        &new_tree,
-        &[&current_commit],
-    )?;
+/// Tree entry name of an edge: source_target_type
+pub fn edge_name(edge: &Edge) -> String {
+    format!(
+        "{}_{}_{}",
+        edge.source,
+        edge.target,
+        edge.edge_type.as_str()
+    )
+}
 
-    Ok(true)
+/// Tree entry name of a conflict record: id_lamport
+pub fn conflict_name(conflict: &Conflict) -> String {
+    format!("{}_{}", conflict.id, conflict.lamport)
 }
 
-/// Delete the snapshot ref (for purge)
+/// Delete the snapshot ref and every remote-tracking snapshot ref (for purge)
 pub fn delete_snapshot(repo: &Repository) -> Result<(), Error> {
-    if let Ok(mut reference) = repo.find_reference(SNAPSHOT_REF) {
-        reference.delete()?;
+    for name in [SNAPSHOT_REF, PULLED_REF] {
+        if let Ok(mut reference) = repo.find_reference(name) {
+            reference.delete()?;
+        }
+    }
+    for reference in repo.references_glob("refs/sterna/remotes/*")? {
+        reference?.delete()?;
     }
     Ok(())
 }
@@ -389,6 +743,39 @@ pub fn get_existing_ids(repo: &Repository) -> Result<std::collections::HashSet<S
     if !is_initialized(repo) {
         return Ok(std::collections::HashSet::new());
     }
-    let issues = load_issues(repo)?;
+    // Deleted ids stay taken so a tombstone is never reused
+    let issues = load_all_issues(repo)?;
     Ok(issues.keys().cloned().collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{create_issue, head, repo};
+
+    #[test]
+    fn test_resolve_at_digits_are_a_hash_and_at_digits_a_timestamp() {
+        let (_dir, repo) = repo();
+        // Make commits until one has an abbreviated hash that is all digits
+        let (commit, prefix) = (0..500)
+            .find_map(|n| {
+                create_issue(&repo, &format!("Issue {n}"));
+                let id = head(&repo).id();
+                let prefix = id.to_string()[..5].to_string();
+                prefix
+                    .bytes()
+                    .all(|b| b.is_ascii_digit())
+                    .then_some((id, prefix))
+            })
+            .unwrap();
+
+        assert_eq!(resolve_at(&repo, &prefix).unwrap().id(), commit);
+        // The same digits as a timestamp are long before the first snapshot
+        assert!(matches!(
+            resolve_at(&repo, &format!("@{prefix}")),
+            Err(Error::NoSnapshotAt(_))
+        ));
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(resolve_at(&repo, &format!("@{now}")).unwrap().id(), commit);
+    }
+}
@@ -1,11 +1,54 @@
-use git2::Repository;
+use std::path::Path;
+
+use git2::{Remote, Repository};
 
 use crate::error::Error;
 
-pub fn get_editor() -> Result<String, Error> {
-    let repo = Repository::discover(".")?;
+pub fn get_editor(repo: &Repository) -> Result<String, Error> {
     let config = repo.config()?;
     config
         .get_string("user.email")
         .map_err(|_| Error::NoIdentity("Set git config user.email".into()))
 }
+
+/// Look up a configured remote by name, or open an anonymous remote when
+/// `spec` is a URL or a path to another repository
+pub fn find_remote<'a>(repo: &'a Repository, spec: &str) -> Result<Remote<'a>, Error> {
+    match repo.find_remote(spec) {
+        Ok(remote) => Ok(remote),
+        Err(_) if is_location(spec) => Ok(repo.remote_anonymous(spec)?),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether `spec` looks like a URL or path rather than a remote name
+pub fn is_location(spec: &str) -> bool {
+    spec.contains([':', '/', '\\']) || Path::new(spec).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_urls_and_paths_are_locations() {
+        for spec in [
+            "https://example.com/issues.git",
+            "file:///srv/issues.git",
+            "git@example.com:team/issues.git",
+            "../sibling",
+            "/srv/issues.git",
+            r"C:\repos\issues",
+            ".",
+        ] {
+            assert!(is_location(spec), "{spec}");
+        }
+    }
+
+    #[test]
+    fn test_remote_names_are_not_locations() {
+        for spec in ["origin", "mirror", "upstream-2"] {
+            assert!(!is_location(spec), "{spec}");
+        }
+    }
+}
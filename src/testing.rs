@@ -0,0 +1,125 @@
+//! Shared test fixtures: issue and edge values, and real repositories on disk
+
+use std::path::Path;
+
+use git2::{Commit, Oid, Repository};
+use tempfile::TempDir;
+
+use crate::commands::{create, init, push};
+use crate::snapshot;
+use crate::types::{
+    Edge, EdgeType, FieldClocks, Issue, IssueType, LabelSet, Priority, SCHEMA_VERSION, Status,
+};
+
+/// An open issue at clock 1, last edited by `a@x`
+pub fn make_issue(id: &str) -> Issue {
+    Issue {
+        schema_version: SCHEMA_VERSION,
+        id: id.to_string(),
+        title: "Original".to_string(),
+        description: String::new(),
+        conflict: false,
+        status: Status::Open,
+        priority: Priority::Medium,
+        issue_type: IssueType::Task,
+        labels: Vec::new(),
+        label_tags: LabelSet::default(),
+        created_at: 100,
+        updated_at: 100,
+        lamport: 1,
+        editor: "a@x".to_string(),
+        claimed: false,
+        claimed_by: None,
+        claim_context: None,
+        claimed_at: None,
+        reason: None,
+        deleted: false,
+        clocks: FieldClocks::at(1),
+    }
+}
+
+/// A live edge at clock 1, written by `a@x`
+pub fn make_edge(source: &str, target: &str, edge_type: EdgeType) -> Edge {
+    Edge {
+        schema_version: SCHEMA_VERSION,
+        source: source.to_string(),
+        target: target.to_string(),
+        edge_type,
+        created_at: 0,
+        lamport: 1,
+        editor: "a@x".to_string(),
+        deleted: false,
+        quarantine: None,
+    }
+}
+
+/// `base` changed by `editor` as a local edit would, with clocks bumped
+pub fn edit(base: &Issue, editor: &str, change: impl FnOnce(&mut Issue)) -> Issue {
+    let mut issue = base.clone();
+    change(&mut issue);
+    issue.record_edit(base, editor.to_string(), 0);
+    issue
+}
+
+/// A repository in `dir/name` with `email` as its editor and
+/// `dir/origin.git` as `origin`
+pub fn open_clone(dir: &Path, name: &str, email: &str) -> Repository {
+    let repo = Repository::init(dir.join(name)).unwrap();
+    let mut config = repo.config().unwrap();
+    config.set_str("user.name", email).unwrap();
+    config.set_str("user.email", email).unwrap();
+    let origin = dir.join("origin.git");
+    repo.remote("origin", origin.to_str().unwrap()).unwrap();
+    repo
+}
+
+/// An initialized repository with `a@x` as its editor
+pub fn repo() -> (TempDir, Repository) {
+    let dir = TempDir::new().unwrap();
+    let repo = open_clone(dir.path(), "a", "a@x");
+    snapshot::init(&repo).unwrap();
+    (dir, repo)
+}
+
+pub fn create_issue(repo: &Repository, title: &str) -> Issue {
+    create::create(
+        repo,
+        title.to_string(),
+        String::new(),
+        Priority::Medium,
+        IssueType::Task,
+        Vec::new(),
+    )
+    .unwrap()
+}
+
+/// A bare origin and two clones sharing one issue; `a` created it
+pub fn clones() -> (TempDir, Repository, Repository, String) {
+    let dir = TempDir::new().unwrap();
+    Repository::init_bare(dir.path().join("origin.git")).unwrap();
+
+    let a = open_clone(dir.path(), "a", "a@x");
+    snapshot::init(&a).unwrap();
+    let issue = create_issue(&a, "Original");
+    push::push(&a, "origin").unwrap();
+
+    let b = open_clone(dir.path(), "b", "b@x");
+    init::init_from(&b, "origin").unwrap();
+    (dir, a, b, issue.id)
+}
+
+pub fn head(repo: &Repository) -> Commit<'_> {
+    snapshot::get_snapshot_commit(repo).unwrap()
+}
+
+/// Every ref and where it points
+pub fn refs(repo: &Repository) -> Vec<(String, Option<Oid>)> {
+    let mut refs: Vec<_> = repo
+        .references()
+        .unwrap()
+        .flatten()
+        .map(|r| (r.name().unwrap().to_string(), r.target()))
+        .collect();
+    refs.sort();
+    refs
+}
@@ -1,42 +1,269 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 use crate::error::Error;
+use crate::migration;
 
-pub const SCHEMA_VERSION: u32 = 1;
+pub const SCHEMA_VERSION: u32 = 8;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Issue {
     pub schema_version: u32,
     pub id: String,
     pub title: String,
     pub description: String,
+    /// Set when a description merge left conflict markers to resolve
+    pub conflict: bool,
     pub status: Status,
     pub priority: Priority,
     pub issue_type: IssueType,
+    /// Live labels, sorted; always derived from `label_tags`
     pub labels: Vec<String>,
+    /// Observed-remove set behind `labels`, merged by union
+    pub label_tags: LabelSet,
     pub created_at: i64,
     pub updated_at: i64,
     pub lamport: u64,
     pub editor: String,
     pub claimed: bool,
+    /// Editor holding the claim, which `editor` stops being once anyone
+    /// else touches the issue
+    pub claimed_by: Option<String>,
     pub claim_context: Option<String>,
     pub claimed_at: Option<u64>,
     pub reason: Option<String>,
+    /// Tombstone: a deleted issue is kept so the deletion survives merges
+    pub deleted: bool,
+    /// Lamport clock of the last edit to each field group
+    pub clocks: FieldClocks,
+}
+
+/// Per-field Lamport clocks, so concurrent edits to different fields merge.
+/// `status` also covers the claim fields and `reason`, and `description` the
+/// `conflict` flag, since they change together.
+/// Labels have no clock: they merge through `LabelSet`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FieldClocks {
+    pub title: u64,
+    pub description: u64,
+    pub status: u64,
+    pub priority: u64,
+    pub issue_type: u64,
+    pub deleted: u64,
+}
+
+impl FieldClocks {
+    /// Every field last written at `lamport`
+    pub fn at(lamport: u64) -> Self {
+        FieldClocks {
+            title: lamport,
+            description: lamport,
+            status: lamport,
+            priority: lamport,
+            issue_type: lamport,
+            deleted: lamport,
+        }
+    }
 }
 
 impl Issue {
     pub fn from_json(data: &[u8]) -> Result<Self, Error> {
-        let value: serde_json::Value = serde_json::from_slice(data)?;
-        let version = value["schema_version"].as_u64().unwrap_or(0) as u32;
-        if version != SCHEMA_VERSION {
-            return Err(Error::SchemaMismatch {
-                expected: SCHEMA_VERSION,
-                found: version,
-            });
-        }
+        Self::from_value(serde_json::from_slice(data)?)
+    }
+
+    /// Decode a payload, upgrading it from older schema versions first
+    pub fn from_value(mut value: serde_json::Value) -> Result<Self, Error> {
+        migration::upgrade_issue(&mut value)?;
         Ok(serde_json::from_value(value)?)
     }
+
+    /// Record a local edit made on top of `before`: move the Lamport clock past
+    /// both the issue and the repo-wide `clock`, stamp every field group that
+    /// changed, and take over authorship. A change to `labels` is recorded as
+    /// adds and removes on top of `before`'s label tags.
+    pub fn record_edit(&mut self, before: &Issue, editor: String, clock: u64) {
+        let lamport = before.lamport.max(clock) + 1;
+        let mut clocks = before.clocks;
+        if self.title != before.title {
+            clocks.title = lamport;
+        }
+        if self.description != before.description || self.conflict != before.conflict {
+            clocks.description = lamport;
+        }
+        if self.status != before.status
+            || self.claimed != before.claimed
+            || self.claimed_by != before.claimed_by
+            || self.claim_context != before.claim_context
+            || self.claimed_at != before.claimed_at
+            || self.reason != before.reason
+        {
+            clocks.status = lamport;
+        }
+        if self.priority != before.priority {
+            clocks.priority = lamport;
+        }
+        if self.issue_type != before.issue_type {
+            clocks.issue_type = lamport;
+        }
+        let mut label_tags = before.label_tags.clone();
+        label_tags.update(&self.labels, &LabelSet::tag(lamport, &editor));
+        if self.deleted != before.deleted {
+            clocks.deleted = lamport;
+        }
+
+        self.lamport = lamport;
+        self.clocks = clocks;
+        self.labels = label_tags.labels();
+        self.label_tags = label_tags;
+        self.updated_at = chrono::Utc::now().timestamp();
+        self.editor = editor;
+    }
+
+    /// Field-level differences going from `old` to `self`
+    pub fn changes_from(&self, old: &Issue) -> Vec<FieldChange> {
+        let mut changes = Vec::new();
+        let mut push = |field: &str, from: String, to: String| {
+            if from != to {
+                changes.push(FieldChange {
+                    field: field.to_string(),
+                    from,
+                    to,
+                });
+            }
+        };
+
+        push("title", old.title.clone(), self.title.clone());
+        push(
+            "description",
+            old.description.clone(),
+            self.description.clone(),
+        );
+        push(
+            "conflict",
+            old.conflict.to_string(),
+            self.conflict.to_string(),
+        );
+        push(
+            "status",
+            old.status.as_str().into(),
+            self.status.as_str().into(),
+        );
+        push(
+            "priority",
+            old.priority.as_str().into(),
+            self.priority.as_str().into(),
+        );
+        push(
+            "type",
+            old.issue_type.as_str().into(),
+            self.issue_type.as_str().into(),
+        );
+        push("labels", old.labels.join(", "), self.labels.join(", "));
+        push("claimed", old.claimed.to_string(), self.claimed.to_string());
+        push(
+            "claimed_by",
+            old.claimed_by.clone().unwrap_or_default(),
+            self.claimed_by.clone().unwrap_or_default(),
+        );
+        push(
+            "claim_context",
+            old.claim_context.clone().unwrap_or_default(),
+            self.claim_context.clone().unwrap_or_default(),
+        );
+        push(
+            "reason",
+            old.reason.clone().unwrap_or_default(),
+            self.reason.clone().unwrap_or_default(),
+        );
+        push("deleted", old.deleted.to_string(), self.deleted.to_string());
+        changes
+    }
+}
+
+/// Observed-remove set of labels. Every add is stamped with a unique tag and
+/// a remove tombstones only the tags it has seen, so merging two sets is a
+/// plain union and an add concurrent with a remove survives.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(transparent)]
+pub struct LabelSet(pub BTreeMap<String, LabelTags>);
+
+/// Add tags of one label, and the ones a remove has observed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct LabelTags {
+    pub added: BTreeSet<String>,
+    pub removed: BTreeSet<String>,
+}
+
+impl LabelSet {
+    /// Tag for an add made by `editor` at `lamport`
+    pub fn tag(lamport: u64, editor: &str) -> String {
+        format!("{lamport}:{editor}")
+    }
+
+    /// Labels with at least one add tag that has not been removed, sorted
+    pub fn labels(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|(_, tags)| tags.added.difference(&tags.removed).next().is_some())
+            .map(|(label, _)| label.clone())
+            .collect()
+    }
+
+    pub fn contains(&self, label: &str) -> bool {
+        self.0
+            .get(label)
+            .is_some_and(|tags| tags.added.difference(&tags.removed).next().is_some())
+    }
+
+    pub fn add(&mut self, label: &str, tag: &str) {
+        self.0
+            .entry(label.to_string())
+            .or_default()
+            .added
+            .insert(tag.to_string());
+    }
+
+    /// Tombstone every add tag of `label` seen so far
+    pub fn remove(&mut self, label: &str) {
+        if let Some(tags) = self.0.get_mut(label) {
+            let added = tags.added.clone();
+            tags.removed.extend(added);
+        }
+    }
+
+    /// Add and remove labels so the live set becomes `labels`
+    pub fn update(&mut self, labels: &[String], tag: &str) {
+        for label in self.labels() {
+            if !labels.contains(&label) {
+                self.remove(&label);
+            }
+        }
+        for label in labels {
+            if !self.contains(label) {
+                self.add(label, tag);
+            }
+        }
+    }
+
+    /// Union of both sides' adds and removes
+    pub fn merge(&mut self, other: &LabelSet) {
+        for (label, tags) in &other.0 {
+            let entry = self.0.entry(label.clone()).or_default();
+            entry.added.extend(tags.added.iter().cloned());
+            entry.removed.extend(tags.removed.iter().cloned());
+        }
+    }
+}
+
+/// A single field that differs between two versions of an issue
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: String,
+    pub to: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -47,6 +274,29 @@ pub enum Status {
     Closed,
 }
 
+impl FromStr for Status {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_lowercase().as_str() {
+            "open" => Ok(Status::Open),
+            "in_progress" | "inprogress" | "in-progress" => Ok(Status::InProgress),
+            "closed" => Ok(Status::Closed),
+            _ => Err(Error::InvalidStatus(s.to_string())),
+        }
+    }
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Open => "open",
+            Status::InProgress => "in_progress",
+            Status::Closed => "closed",
+        }
+    }
+}
+
 #[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
 pub enum Priority {
@@ -57,8 +307,10 @@ pub enum Priority {
     Backlog = 4,
 }
 
-impl Priority {
-    pub fn from_str(s: &str) -> Result<Self, Error> {
+impl FromStr for Priority {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
         match s.to_lowercase().as_str() {
             "critical" | "0" => Ok(Priority::Critical),
             "high" | "1" => Ok(Priority::High),
@@ -68,7 +320,9 @@ impl Priority {
             _ => Err(Error::InvalidPriority(s.to_string())),
         }
     }
+}
 
+impl Priority {
     pub fn as_str(&self) -> &'static str {
         match self {
             Priority::Critical => "critical",
@@ -90,8 +344,10 @@ pub enum IssueType {
     Chore,
 }
 
-impl IssueType {
-    pub fn from_str(s: &str) -> Result<Self, Error> {
+impl FromStr for IssueType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
         match s.to_lowercase().as_str() {
             "epic" => Ok(IssueType::Epic),
             "task" => Ok(IssueType::Task),
@@ -101,7 +357,9 @@ impl IssueType {
             _ => Err(Error::InvalidIssueType(s.to_string())),
         }
     }
+}
 
+impl IssueType {
     pub fn as_str(&self) -> &'static str {
         match self {
             IssueType::Epic => "epic",
@@ -113,27 +371,51 @@ impl IssueType {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Edge {
     pub schema_version: u32,
     pub source: String,
     pub target: String,
     pub edge_type: EdgeType,
     pub created_at: i64,
+    pub lamport: u64,
+    pub editor: String,
+    /// Tombstone: a removed edge is kept so the removal survives merges
+    pub deleted: bool,
+    /// Why a pull set this edge aside (it closed a cycle or lost an issue);
+    /// a quarantined edge is kept but not live until `st quarantine resolve`
+    pub quarantine: Option<String>,
 }
 
 impl Edge {
     pub fn from_json(data: &[u8]) -> Result<Self, Error> {
-        let value: serde_json::Value = serde_json::from_slice(data)?;
-        let version = value["schema_version"].as_u64().unwrap_or(0) as u32;
-        if version != SCHEMA_VERSION {
-            return Err(Error::SchemaMismatch {
-                expected: SCHEMA_VERSION,
-                found: version,
-            });
-        }
+        Self::from_value(serde_json::from_slice(data)?)
+    }
+
+    /// Decode a payload, upgrading it from older schema versions first
+    pub fn from_value(mut value: serde_json::Value) -> Result<Self, Error> {
+        migration::upgrade_edge(&mut value)?;
         Ok(serde_json::from_value(value)?)
     }
+
+    /// Record a local change to this edge: move its Lamport clock past both its
+    /// own and the repo-wide `clock`, and take over authorship.
+    pub fn record_edit(&mut self, editor: String, clock: u64) {
+        self.lamport = self.lamport.max(clock) + 1;
+        self.editor = editor;
+    }
+
+    /// Neither removed nor quarantined
+    pub fn is_live(&self) -> bool {
+        !self.deleted && self.quarantine.is_none()
+    }
+
+    /// Whether two edges connect the same issues with the same type
+    pub fn same_key(&self, other: &Edge) -> bool {
+        self.source == other.source
+            && self.target == other.target
+            && self.edge_type == other.edge_type
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -158,12 +440,132 @@ impl EdgeType {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Two concurrent versions of an issue where a merge kept only one side's
+/// value for some field groups, stored as `conflicts/<id>_<lamport>`. Every
+/// merge that discards something writes a new record, and resolving one
+/// keeps it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub schema_version: u32,
+    /// Issue id
+    pub id: String,
+    pub title: String,
+    /// Field groups whose losing value was discarded: title, description,
+    /// status, priority, type
+    pub fields: Vec<String>,
+    /// The version this clone had before the merge
+    pub local: Issue,
+    /// The version that came in
+    pub remote: Issue,
+    /// Where the remote version came from, e.g. `pull from origin`
+    pub source: String,
+    pub created_at: i64,
+    /// Clock of the merge that recorded it; with `id`, names the entry
+    pub lamport: u64,
+    /// Who recorded it, or resolved it once resolved
+    pub editor: String,
+    /// How `st conflicts resolve` settled it; kept so the resolution survives
+    /// merges with clones that still have it open
+    pub resolution: Option<Resolution>,
+}
+
+impl Conflict {
+    pub fn from_json(data: &[u8]) -> Result<Self, Error> {
+        Self::from_value(serde_json::from_slice(data)?)
+    }
+
+    /// Decode a record, upgrading it and its embedded issues from older
+    /// schema versions first
+    pub fn from_value(mut value: serde_json::Value) -> Result<Self, Error> {
+        migration::upgrade_conflict(&mut value)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// An open record of merging `remote` into `local` discarding `fields`
+    pub fn record(
+        local: &Issue,
+        remote: &Issue,
+        fields: Vec<String>,
+        source: &str,
+        editor: &str,
+        lamport: u64,
+    ) -> Self {
+        Conflict {
+            schema_version: SCHEMA_VERSION,
+            id: local.id.clone(),
+            title: local.title.clone(),
+            fields,
+            local: local.clone(),
+            remote: remote.clone(),
+            source: source.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            lamport,
+            editor: editor.to_string(),
+            resolution: None,
+        }
+    }
+}
+
+/// Which version `st conflicts resolve` keeps
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Local,
+    Remote,
+    /// The merge result as it stands
+    Merged,
+}
+
+impl FromStr for Resolution {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_lowercase().as_str() {
+            "local" => Ok(Resolution::Local),
+            "remote" => Ok(Resolution::Remote),
+            "merged" => Ok(Resolution::Merged),
+            _ => Err(Error::InvalidResolution(s.to_string())),
+        }
+    }
+}
+
+impl Resolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Local => "local",
+            Resolution::Remote => "remote",
+            Resolution::Merged => "merged",
+        }
+    }
+}
+
+/// Snapshot metadata, stored as the `meta` blob next to `issues/` and `edges/`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct Snapshot {
     pub schema_version: u32,
+    /// Incremented on every snapshot write
     pub version: u64,
     pub created_at: i64,
+    /// Repo-wide Lamport clock: the highest value written or witnessed so far
     pub lamport: u64,
+    /// Manifest: blob ids of every `issues/` entry, in tree order
     pub issue_hashes: Vec<String>,
+    /// Manifest: blob ids of every `edges/` entry, in tree order
     pub edge_hashes: Vec<String>,
+    /// Manifest: blob ids of every `conflicts/` entry, in tree order
+    #[serde(default)]
+    pub conflict_hashes: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn from_json(data: &[u8]) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(data)?)
+    }
+
+    /// Whether two snapshots hold exactly the same issues, edges and conflicts
+    pub fn same_content(&self, other: &Snapshot) -> bool {
+        self.issue_hashes == other.issue_hashes
+            && self.edge_hashes == other.edge_hashes
+            && self.conflict_hashes == other.conflict_hashes
+    }
 }